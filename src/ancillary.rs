@@ -0,0 +1,221 @@
+//! Ancillary chunks
+
+use crate::*;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use color::RGBA8888;
+
+/// Image offset (`oFFs` chunk)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageOffset {
+    pub x: i32,
+    pub y: i32,
+    pub unit: OffsetUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetUnit {
+    Pixel,
+    Micrometer,
+}
+
+impl ImageOffset {
+    /// Parses the data of the `oFFs` chunk.
+    pub fn parse(data: &[u8]) -> Result<Self, DecodeError> {
+        if data.len() != 9 {
+            return Err(DecodeError::InvalidData);
+        }
+        let x = Be32(data[0..4].try_into().unwrap()).as_u32() as i32;
+        let y = Be32(data[4..8].try_into().unwrap()).as_u32() as i32;
+        let unit = match data[8] {
+            0 => OffsetUnit::Pixel,
+            1 => OffsetUnit::Micrometer,
+            _ => return Err(DecodeError::InvalidData),
+        };
+        Ok(Self { x, y, unit })
+    }
+}
+
+/// Parses the data of the `iCCP` chunk and returns the decompressed ICC profile.
+#[cfg(feature = "alloc")]
+pub(crate) fn parse_icc_profile(data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    // profile name (1-79 bytes), null separator, compression method, compressed profile
+    let name_len = data
+        .iter()
+        .position(|&v| v == 0)
+        .ok_or(DecodeError::InvalidData)?;
+    if !(1..=79).contains(&name_len) {
+        return Err(DecodeError::InvalidData);
+    }
+    let Some((&compression_method, compressed)) = data[name_len + 1..].split_first() else {
+        return Err(DecodeError::InvalidData);
+    };
+    if compression_method != 0 {
+        return Err(DecodeError::UnsupportedCompressionMethod(
+            compression_method,
+        ));
+    }
+    check_zlib_header(compressed)?;
+    // deflate cannot expand data by more than 1032 times
+    let max_len = compressed.len().saturating_mul(1032);
+    Deflate::inflate(compressed, max_len).map_err(|_| DecodeError::InvalidData)
+}
+
+/// Parses the data of the `bKGD` chunk and returns the background color.
+///
+/// The palette must have been read before, since index color format images refer to a palette entry.
+/// Returns `None` if the length does not match the image type or the index is out of the palette.
+#[cfg(feature = "alloc")]
+pub(crate) fn parse_background(
+    data: &[u8],
+    info: &ImageInfo,
+    palette: &[RGB888],
+) -> Option<RGBA8888> {
+    let sample = |index: usize| {
+        info.bit_depth
+            .scale_to_8bit(Be16([data[index * 2], data[index * 2 + 1]]).as_u16())
+    };
+    match info.image_type {
+        ImageType::Grayscale | ImageType::GrayscaleAlpha if data.len() == 2 => {
+            Some(RGBA8888::from_gray(sample(0)))
+        }
+        ImageType::RGB | ImageType::RGBA if data.len() == 6 => {
+            Some(RGBA8888::from_rgb(sample(0), sample(1), sample(2)))
+        }
+        ImageType::Indexed if data.len() == 1 => {
+            palette.get(data[0] as usize).map(|color| color.into_rgba())
+        }
+        _ => None,
+    }
+}
+
+/// Suggested palette (`sPLT` chunk)
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuggestedPalette {
+    /// The palette name, decoded from Latin-1
+    pub name: String,
+    /// The sample depth of the entries, either 8 or 16
+    pub sample_depth: u8,
+    pub entries: Vec<SuggestedPaletteEntry>,
+}
+
+/// An entry of a suggested palette
+///
+/// For 8-bit sample depth, the color and alpha values are in the range `0..=255`.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuggestedPaletteEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    pub frequency: u16,
+}
+
+#[cfg(feature = "alloc")]
+impl SuggestedPalette {
+    /// Parses the data of the `sPLT` chunk.
+    pub fn parse(data: &[u8]) -> Result<Self, DecodeError> {
+        // palette name (1-79 bytes), null separator, sample depth, entries
+        let name_len = data
+            .iter()
+            .position(|&v| v == 0)
+            .ok_or(DecodeError::InvalidData)?;
+        if !(1..=79).contains(&name_len) {
+            return Err(DecodeError::InvalidData);
+        }
+        let name = data[..name_len].iter().map(|&v| v as char).collect();
+        let Some((&sample_depth, entries)) = data[name_len + 1..].split_first() else {
+            return Err(DecodeError::InvalidData);
+        };
+        let entry_size = match sample_depth {
+            8 => 6,
+            16 => 10,
+            _ => return Err(DecodeError::InvalidData),
+        };
+        if entries.len() % entry_size != 0 {
+            return Err(DecodeError::InvalidData);
+        }
+        let entries = entries
+            .chunks_exact(entry_size)
+            .map(|entry| {
+                let (color, frequency) = entry.split_at(entry_size - 2);
+                let sample = |index: usize| {
+                    if sample_depth == 8 {
+                        color[index] as u16
+                    } else {
+                        Be16([color[index * 2], color[index * 2 + 1]]).as_u16()
+                    }
+                };
+                SuggestedPaletteEntry {
+                    red: sample(0),
+                    green: sample(1),
+                    blue: sample(2),
+                    alpha: sample(3),
+                    frequency: Be16(frequency.try_into().unwrap()).as_u16(),
+                }
+            })
+            .collect();
+        Ok(Self {
+            name,
+            sample_depth,
+            entries,
+        })
+    }
+}
+
+/// GIF graphic control extension (`gIFg` chunk)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GifGraphicControl {
+    /// The disposal method of the GIF graphic control extension (0-7)
+    pub disposal_method: u8,
+    pub user_input: bool,
+    /// The delay time in 1/100 seconds
+    pub delay_time: u16,
+}
+
+impl GifGraphicControl {
+    /// Parses the data of the `gIFg` chunk.
+    pub fn parse(data: &[u8]) -> Result<Self, DecodeError> {
+        let Ok(data) = <&[u8; 4]>::try_from(data) else {
+            return Err(DecodeError::InvalidData);
+        };
+        Ok(Self {
+            disposal_method: data[0],
+            user_input: data[1] != 0,
+            delay_time: Be16([data[2], data[3]]).as_u16(),
+        })
+    }
+}
+
+/// GIF application extension (`gIFx` chunk)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GifApplicationExtension<'a> {
+    pub identifier: [u8; 8],
+    pub authentication_code: [u8; 3],
+    pub data: &'a [u8],
+}
+
+impl<'a> GifApplicationExtension<'a> {
+    /// Parses the data of the `gIFx` chunk.
+    pub fn parse(data: &'a [u8]) -> Result<Self, DecodeError> {
+        let Some((header, data)) = data.split_at_checked(11) else {
+            return Err(DecodeError::InvalidData);
+        };
+        Ok(Self {
+            identifier: header[..8].try_into().unwrap(),
+            authentication_code: header[8..].try_into().unwrap(),
+            data,
+        })
+    }
+}
+
+/// GIF extensions carried over from a GIF file
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GifExtensions<'a> {
+    pub graphic_controls: Vec<GifGraphicControl>,
+    pub applications: Vec<GifApplicationExtension<'a>>,
+}
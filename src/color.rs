@@ -51,6 +51,43 @@ impl RGBA8888 {
         components.r() == components.g() && components.g() == components.b()
     }
 
+    /// Returns the luminance of the color (ITU-R BT.601).
+    #[inline]
+    pub const fn luminance(&self) -> u8 {
+        let components = self.components();
+        ((components.r() as u32 * 299
+            + components.g() as u32 * 587
+            + components.b() as u32 * 114
+            + 500)
+            / 1000) as u8
+    }
+
+    /// Composites this color over `dst` (Porter-Duff source-over, straight alpha).
+    ///
+    /// With `Fa = Sa + Da * (1 - Sa)`, each color component is `(Sc * Sa + Dc * Da * (1 - Sa)) / Fa`.
+    /// All results are rounded to the nearest integer, and a fully transparent result is `0, 0, 0, 0`.
+    pub const fn blend_over(&self, dst: Self) -> Self {
+        const fn blend(src: u8, dst: u8, src_weight: u32, dst_weight: u32, total: u32) -> u8 {
+            ((src as u32 * src_weight + dst as u32 * dst_weight + total / 2) / total) as u8
+        }
+
+        let src = self.components();
+        let dst = dst.components();
+        // weights are scaled by 255 * 255
+        let src_weight = src.a() as u32 * 255;
+        let dst_weight = dst.a() as u32 * (255 - src.a() as u32);
+        let total = src_weight + dst_weight;
+        if total == 0 {
+            return Self::from_rgba(0, 0, 0, 0);
+        }
+        Self::from_rgba(
+            blend(src.r(), dst.r(), src_weight, dst_weight, total),
+            blend(src.g(), dst.g(), src_weight, dst_weight, total),
+            blend(src.b(), dst.b(), src_weight, dst_weight, total),
+            ((total + 127) / 255) as u8,
+        )
+    }
+
     #[inline]
     pub const fn from_gray(gray: u8) -> Self {
         Self((gray as u32) * 0x00010101 | 0xFF000000)
@@ -71,6 +108,35 @@ impl RGBA8888 {
         RGBAComponents8888::new(r, g, b, a).into_rgba()
     }
 
+    /// Creates a color from bytes in R, G, B, A order, regardless of the target endianness.
+    #[inline]
+    pub const fn from_rgba_bytes(bytes: [u8; 4]) -> Self {
+        Self(u32::from_le_bytes(bytes))
+    }
+
+    /// Returns the bytes in R, G, B, A order, regardless of the target endianness.
+    #[inline]
+    pub const fn to_rgba_bytes_array(&self) -> [u8; 4] {
+        self.0.to_le_bytes()
+    }
+
+    /// Returns the channels in alpha, blue, green, red order, regardless of the target endianness.
+    ///
+    /// This is the packed value in big endian, the reverse of [`Self::to_rgba_bytes_array`].
+    #[inline]
+    pub const fn to_be_bytes(&self) -> [u8; 4] {
+        let [r, g, b, a] = self.to_rgba_bytes_array();
+        [a, b, g, r]
+    }
+
+    /// Returns the channels in red, green, blue, alpha order, regardless of the target endianness.
+    ///
+    /// This is the packed value in little endian, and the same layout as [`Self::to_rgba_bytes_array`].
+    #[inline]
+    pub const fn to_le_bytes(&self) -> [u8; 4] {
+        self.to_rgba_bytes_array()
+    }
+
     #[inline]
     pub const fn to_rgb(&self) -> RGB888 {
         let components = self.components();
@@ -116,7 +182,10 @@ impl Ord for RGBA8888 {
     }
 }
 
+// The field order follows the byte order of `RGBA8888`,
+// whose inner value is `a << 24 | b << 16 | g << 8 | r` on all targets.
 #[cfg(target_endian = "little")]
+#[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct RGBAComponents8888 {
     r: u8,
@@ -125,6 +194,16 @@ pub struct RGBAComponents8888 {
     a: u8,
 }
 
+#[cfg(target_endian = "big")]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RGBAComponents8888 {
+    a: u8,
+    b: u8,
+    g: u8,
+    r: u8,
+}
+
 impl RGBAComponents8888 {
     #[inline]
     pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
@@ -307,4 +386,40 @@ impl RGB888 {
             b: gray,
         }
     }
+
+    /// Packs the color into 16 bits as RGB565, keeping the most significant bits of each component.
+    #[inline]
+    pub const fn to_rgb565(&self) -> u16 {
+        ((self.r as u16 >> 3) << 11) | ((self.g as u16 >> 2) << 5) | (self.b as u16 >> 3)
+    }
+
+    /// Formats the color as `#RRGGBB` with uppercase hexadecimal digits.
+    ///
+    /// The result is ASCII, so it can be converted with [`core::str::from_utf8`] without allocation.
+    pub const fn to_hex(&self) -> [u8; 7] {
+        const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+        let mut result = [b'#'; 7];
+        let components = [self.r, self.g, self.b];
+        let mut i = 0;
+        while i < 3 {
+            result[1 + i * 2] = DIGITS[(components[i] >> 4) as usize];
+            result[2 + i * 2] = DIGITS[(components[i] & 15) as usize];
+            i += 1;
+        }
+        result
+    }
+
+    /// Parses a color in the form of `#RRGGBB`, with either uppercase or lowercase hexadecimal digits.
+    pub fn from_hex(s: &str) -> Option<Self> {
+        let digits = s.strip_prefix('#')?;
+        if digits.len() != 6 || !digits.bytes().all(|v| v.is_ascii_hexdigit()) {
+            return None;
+        }
+        let value = u32::from_str_radix(digits, 16).ok()?;
+        Some(Self::new(
+            (value >> 16) as u8,
+            (value >> 8) as u8,
+            value as u8,
+        ))
+    }
 }
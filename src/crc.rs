@@ -0,0 +1,63 @@
+//! CRC-32 used by the PNG chunks
+
+/// Streaming CRC-32 calculator (ISO 3309, polynomial `0xEDB88320`)
+///
+/// The CRC of a chunk is calculated over the chunk type and the chunk data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut c = i as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 {
+                    0xEDB8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+                k += 1;
+            }
+            table[i] = c;
+            i += 1;
+        }
+        table
+    };
+
+    #[inline]
+    pub const fn new() -> Self {
+        Self(0xFFFF_FFFF)
+    }
+
+    #[inline]
+    pub fn update(&mut self, bytes: &[u8]) {
+        let mut c = self.0;
+        for &byte in bytes {
+            c = Self::TABLE[((c ^ byte as u32) & 0xFF) as usize] ^ (c >> 8);
+        }
+        self.0 = c;
+    }
+
+    #[inline]
+    pub const fn finalize(self) -> u32 {
+        self.0 ^ 0xFFFF_FFFF
+    }
+
+    /// Calculates the CRC of the specified bytes at once.
+    #[inline]
+    pub fn checksum(bytes: &[u8]) -> u32 {
+        let mut crc = Self::new();
+        crc.update(bytes);
+        crc.finalize()
+    }
+}
+
+impl Default for Crc32 {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,589 @@
+//! PNG encoders
+//!
+//! The image stream is compressed with fixed Huffman codes and back references within the last
+//! 32K bytes. This is faster but less thorough than general-purpose deflate compressors,
+//! and the filter types chosen for the scanlines affect the size as they would there.
+
+use crate::*;
+use alloc::vec;
+use filter::filter_line;
+
+/// Length of the filtered image data compressed into each deflate block and IDAT chunk
+const BLOCK_SIZE: usize = 0x10000;
+
+/// Errors that can occur while encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The image information is invalid, or describes an interlaced image
+    InvalidImageInfo,
+    /// The palette is missing for an index color format image, has more entries than the bit depth allows,
+    /// or is given for a grayscale image
+    InvalidPalette,
+    /// The alpha table is given for an image other than index color format, or is longer than the palette
+    InvalidTransparency,
+    /// The length of a scanline is not [`ImageInfo::stride`]
+    InvalidRowLength,
+    /// More scanlines than the image height were written
+    TooManyRows,
+    /// Fewer scanlines than the image height were written before finishing
+    MissingRows,
+}
+
+/// How the encoder chooses the filter type of each scanline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStrategy {
+    /// The same filter type for every scanline
+    Fixed(FilterType),
+    /// The filter type that gives the minimum sum of absolute differences, chosen for each scanline
+    ///
+    /// The filtered bytes are taken as signed values, as recommended by the specification.
+    MinSumAbs,
+}
+
+impl Default for FilterStrategy {
+    #[inline]
+    fn default() -> Self {
+        Self::Fixed(FilterType::None)
+    }
+}
+
+/// Encodes decoded images into PNG files.
+///
+/// The image type, bit depth and palette of the decoded image are preserved,
+/// and 16-bit images are written with full precision.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PngEncoder {
+    filter: FilterStrategy,
+}
+
+impl PngEncoder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an encoder that filters the scanlines with the specified strategy.
+    #[inline]
+    pub fn with_filter(filter: FilterStrategy) -> Self {
+        Self { filter }
+    }
+
+    #[inline]
+    pub fn filter(&self) -> FilterStrategy {
+        self.filter
+    }
+
+    /// Encodes the image and returns the PNG file.
+    pub fn encode(&self, image: &ImageData) -> Result<Vec<u8>, EncodeError> {
+        let info = image.info;
+        let mut output = Vec::new();
+        let mut encoder =
+            PngStreamEncoder::new(info, |bytes: &[u8]| output.extend_from_slice(bytes))?
+                .filter_strategy(self.filter);
+        match info.image_type {
+            ImageType::Indexed => {
+                encoder = encoder
+                    .palette(&image.palette)
+                    .transparency(&image.transparency);
+            }
+            ImageType::RGB | ImageType::RGBA => {
+                encoder = encoder.palette(&image.palette);
+            }
+            ImageType::Grayscale | ImageType::GrayscaleAlpha => {}
+        }
+        let mut row = Vec::with_capacity(info.stride());
+        for y in 0..info.height as usize {
+            row.clear();
+            pack_row(image, y, &mut row);
+            encoder.write_row(&row)?;
+        }
+        encoder.finish()?;
+        Ok(output)
+    }
+}
+
+/// Converts a row of the decoded image back to the layout of the PNG image stream.
+fn pack_row(image: &ImageData, y: usize, output: &mut Vec<u8>) {
+    let info = &image.info;
+    let row_len = info.width as usize * info.image_type.n_channels();
+    let samples = &image.data[y * row_len..(y + 1) * row_len];
+    match info.bit_depth {
+        BitDepth::Bpp8 => output.extend_from_slice(samples),
+        BitDepth::Bpp16 => match image.data16.get(y * row_len..(y + 1) * row_len) {
+            Some(samples16) => {
+                for sample in samples16 {
+                    output.extend_from_slice(&sample.to_be_bytes());
+                }
+            }
+            None => {
+                // the full precision is not available, so the most significant byte is repeated
+                for &sample in samples {
+                    output.extend_from_slice(&[sample, sample]);
+                }
+            }
+        },
+        BitDepth::Bpp1 | BitDepth::Bpp2 | BitDepth::Bpp4 => {
+            let bits = info.bit_depth.bits_per_pixel();
+            // grayscale samples were scaled to the full 8-bit range by the decoder
+            let scale = if info.image_type == ImageType::Grayscale {
+                (0xFF / info.bit_depth.max_value()) as u8
+            } else {
+                1
+            };
+            for chunk in samples.chunks((8 / bits) as usize) {
+                let mut byte = 0;
+                for (i, &sample) in chunk.iter().enumerate() {
+                    byte |= (sample / scale) << (8 - bits * (i as u8 + 1));
+                }
+                output.push(byte);
+            }
+        }
+    }
+}
+
+/// Encodes a PNG image row by row, passing the encoded bytes to a sink as soon as they are ready.
+///
+/// The image stream is compressed as the scanlines arrive and written in an IDAT chunk every 64K bytes
+/// of filtered data, so the whole image is never held in memory.
+pub struct PngStreamEncoder<F: FnMut(&[u8])> {
+    info: ImageInfo,
+    palette: Vec<RGB888>,
+    transparency: Vec<u8>,
+    filter: FilterStrategy,
+    sink: F,
+    prev_line: Vec<u8>,
+    /// Work area for trying filter types
+    scratch: Vec<u8>,
+    /// The filter type byte and the filtered bytes of the current scanline
+    filtered: Vec<u8>,
+    deflater: Deflater,
+    adler: Adler32,
+    zlib_started: bool,
+    rows_written: u32,
+}
+
+impl<F: FnMut(&[u8])> PngStreamEncoder<F> {
+    /// Creates an encoder for an image with the specified information.
+    ///
+    /// Returns an error if the image information is invalid or interlaced.
+    pub fn new(info: ImageInfo, sink: F) -> Result<Self, EncodeError> {
+        info.validate().map_err(|_| EncodeError::InvalidImageInfo)?;
+        Ok(Self {
+            info,
+            palette: Vec::new(),
+            transparency: Vec::new(),
+            filter: FilterStrategy::default(),
+            sink,
+            prev_line: Vec::new(),
+            scratch: Vec::new(),
+            filtered: Vec::new(),
+            deflater: Deflater::new(),
+            adler: Adler32::new(),
+            zlib_started: false,
+            rows_written: 0,
+        })
+    }
+
+    /// Sets the palette written to the PLTE chunk.
+    ///
+    /// Required for index color format images, optional as a suggested palette for truecolor images.
+    #[inline]
+    pub fn palette(mut self, palette: &[RGB888]) -> Self {
+        self.palette = palette.to_vec();
+        self
+    }
+
+    /// Sets the alpha table written to the tRNS chunk of index color format images.
+    ///
+    /// The table may be shorter than the palette, in which case the remaining entries are opaque.
+    #[inline]
+    pub fn transparency(mut self, transparency: &[u8]) -> Self {
+        self.transparency = transparency.to_vec();
+        self
+    }
+
+    /// Sets the filter type applied to every scanline (default: [`FilterType::None`]).
+    #[inline]
+    pub fn filter_type(mut self, filter_type: FilterType) -> Self {
+        self.filter = FilterStrategy::Fixed(filter_type);
+        self
+    }
+
+    /// Sets how the filter type of each scanline is chosen.
+    #[inline]
+    pub fn filter_strategy(mut self, filter: FilterStrategy) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    #[inline]
+    pub fn info(&self) -> &ImageInfo {
+        &self.info
+    }
+
+    /// Writes the next scanline.
+    ///
+    /// The scanline has the layout of the PNG image stream, that is, [`ImageInfo::stride`] bytes
+    /// with samples less than 8 bits packed and 16-bit samples in big endian.
+    /// The signature and the header chunks are written before the first scanline.
+    pub fn write_row(&mut self, row: &[u8]) -> Result<(), EncodeError> {
+        if self.rows_written >= self.info.height {
+            return Err(EncodeError::TooManyRows);
+        }
+        if row.len() != self.info.stride() {
+            return Err(EncodeError::InvalidRowLength);
+        }
+        if self.rows_written == 0 {
+            self.write_header()?;
+        }
+
+        // distance to the corresponding byte of the left pixel
+        let bpp = if self.info.bit_depth > BitDepth::Bpp8 {
+            self.info.image_type.n_channels() * 2
+        } else {
+            self.info.image_type.n_channels()
+        };
+        let filter_type = match self.filter {
+            FilterStrategy::Fixed(filter_type) => filter_type,
+            FilterStrategy::MinSumAbs => {
+                let mut best = (FilterType::None, u64::MAX);
+                for filter_type in (0..5).filter_map(FilterType::new) {
+                    self.scratch.clear();
+                    filter_line(filter_type, bpp, row, &self.prev_line, &mut self.scratch);
+                    let sum = self
+                        .scratch
+                        .iter()
+                        .map(|&v| (v as i8).unsigned_abs() as u64)
+                        .sum();
+                    if sum < best.1 {
+                        best = (filter_type, sum);
+                    }
+                }
+                best.0
+            }
+        };
+        self.filtered.clear();
+        self.filtered.push(filter_type.as_u8());
+        filter_line(filter_type, bpp, row, &self.prev_line, &mut self.filtered);
+        self.adler.update(&self.filtered);
+        self.deflater.write(&self.filtered);
+        self.prev_line.copy_from_slice(row);
+        self.rows_written += 1;
+
+        if self.deflater.pending_len() >= BLOCK_SIZE {
+            self.deflater.compress_block(false);
+            self.write_idat(false);
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining image data and the IEND chunk.
+    ///
+    /// Returns an error if fewer scanlines than the image height have been written.
+    pub fn finish(mut self) -> Result<(), EncodeError> {
+        if self.rows_written != self.info.height {
+            return Err(EncodeError::MissingRows);
+        }
+        self.deflater.compress_block(true);
+        self.write_idat(true);
+        write_chunk(&mut self.sink, FourCC::IEND, &[]);
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> Result<(), EncodeError> {
+        let has_palette = !self.palette.is_empty();
+        match self.info.image_type {
+            ImageType::Indexed => {
+                if !has_palette || self.palette.len() > 1 << self.info.bit_depth.bits_per_pixel() {
+                    return Err(EncodeError::InvalidPalette);
+                }
+            }
+            ImageType::RGB | ImageType::RGBA => {
+                if self.palette.len() > 256 {
+                    return Err(EncodeError::InvalidPalette);
+                }
+            }
+            ImageType::Grayscale | ImageType::GrayscaleAlpha => {
+                if has_palette {
+                    return Err(EncodeError::InvalidPalette);
+                }
+            }
+        }
+        if !self.transparency.is_empty()
+            && (self.info.image_type != ImageType::Indexed
+                || self.transparency.len() > self.palette.len())
+        {
+            return Err(EncodeError::InvalidTransparency);
+        }
+
+        (self.sink)(PNG_SIGNATURE);
+        write_chunk(&mut self.sink, FourCC::IHDR, &self.info.to_ihdr_data());
+        if has_palette {
+            let plte = self
+                .palette
+                .iter()
+                .flat_map(|color| [color.r, color.g, color.b])
+                .collect::<Vec<_>>();
+            write_chunk(&mut self.sink, FourCC::PLTE, &plte);
+        }
+        if !self.transparency.is_empty() {
+            write_chunk(&mut self.sink, FourCC::tRNS, &self.transparency);
+        }
+        self.prev_line = vec![0; self.info.stride()];
+        Ok(())
+    }
+
+    /// Writes an IDAT chunk with the compressed bytes produced so far.
+    fn write_idat(&mut self, is_final: bool) {
+        let mut data = Vec::new();
+        if !self.zlib_started {
+            // zlib header: deflate with 32K window, no preset dictionary, fastest compression
+            data.extend_from_slice(&[0x78, 0x01]);
+            self.zlib_started = true;
+        }
+        data.append(&mut self.deflater.output);
+        if is_final {
+            data.extend_from_slice(&self.adler.finalize().to_be_bytes());
+        }
+        write_chunk(&mut self.sink, FourCC::IDAT, &data);
+    }
+}
+
+/// Writes a chunk with its length and CRC.
+fn write_chunk<F: FnMut(&[u8])>(sink: &mut F, chunk_type: FourCC, data: &[u8]) {
+    let mut crc = Crc32::new();
+    crc.update(&chunk_type.0);
+    crc.update(data);
+    sink(&Be32::from_u32(data.len() as u32).0);
+    sink(&chunk_type.0);
+    sink(data);
+    sink(&Be32::from_u32(crc.finalize()).0);
+}
+
+/// Adler-32 checksum at the end of the zlib stream
+#[derive(Debug, Clone, Copy)]
+struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    const MOD: u32 = 65521;
+
+    #[inline]
+    const fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        // 5552 is the largest count of bytes for which `b` cannot overflow
+        for chunk in bytes.chunks(5552) {
+            for &byte in chunk {
+                self.a += byte as u32;
+                self.b += self.a;
+            }
+            self.a %= Self::MOD;
+            self.b %= Self::MOD;
+        }
+    }
+
+    #[inline]
+    const fn finalize(self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+/// Number of bits of the hash of 3 bytes that looks up back references
+const HASH_BITS: u32 = 15;
+/// Maximum distance of a back reference
+const WINDOW_SIZE: usize = 0x8000;
+/// Maximum length of a back reference
+const MAX_MATCH: usize = 258;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Deflate compressor using fixed Huffman codes
+///
+/// The input is compressed block by block as it arrives, and back references are looked up
+/// through a hash table of the most recent position of each 3 bytes.
+struct Deflater {
+    /// The window of already compressed bytes followed by the bytes not yet compressed
+    buf: Vec<u8>,
+    /// Start of the bytes not yet compressed in `buf`
+    pos: usize,
+    /// Number of bytes dropped from the front of `buf`
+    base: usize,
+    /// Position in the whole input plus one of each hash, zero if none
+    head: Vec<usize>,
+    bit_buf: u64,
+    bit_count: u32,
+    /// Compressed bytes not yet written
+    output: Vec<u8>,
+}
+
+impl Deflater {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            pos: 0,
+            base: 0,
+            head: Vec::new(),
+            bit_buf: 0,
+            bit_count: 0,
+            output: Vec::new(),
+        }
+    }
+
+    #[inline]
+    fn write(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Returns the number of bytes not yet compressed.
+    #[inline]
+    fn pending_len(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    /// Compresses the pending bytes into a block.
+    ///
+    /// The final block is padded to a byte boundary, otherwise the last bits are kept for the next block.
+    fn compress_block(&mut self, is_final: bool) {
+        if self.head.is_empty() {
+            self.head = vec![0; 1 << HASH_BITS];
+        }
+        // BFINAL and BTYPE = 01 (fixed Huffman codes)
+        self.put_bits(is_final as u32 | 0b10, 3);
+        let end = self.buf.len();
+        let mut i = self.pos;
+        while i < end {
+            let (len, distance) = self.find_match(i, end);
+            if len >= 3 {
+                self.put_length(len);
+                self.put_distance(distance);
+                for j in i + 1..(i + len).min(end - 2) {
+                    let hash = self.hash(j);
+                    self.head[hash] = self.base + j + 1;
+                }
+                i += len;
+            } else {
+                self.put_symbol(self.buf[i] as usize);
+                i += 1;
+            }
+        }
+        // end of block
+        self.put_symbol(256);
+        if is_final && self.bit_count > 0 {
+            self.output.push(self.bit_buf as u8);
+            self.bit_buf = 0;
+            self.bit_count = 0;
+        }
+
+        // only the window is kept for the next block
+        self.pos = end;
+        if let Some(excess) = self.buf.len().checked_sub(WINDOW_SIZE) {
+            self.buf.drain(..excess);
+            self.base += excess;
+            self.pos -= excess;
+        }
+    }
+
+    #[inline]
+    fn hash(&self, i: usize) -> usize {
+        let value = u32::from_le_bytes([self.buf[i], self.buf[i + 1], self.buf[i + 2], 0]);
+        (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+    }
+
+    /// Looks up the previous occurrence of the bytes at `i` and registers this position.
+    ///
+    /// Returns the length and distance of the match, or zero length if there is none.
+    fn find_match(&mut self, i: usize, end: usize) -> (usize, usize) {
+        if end - i < 3 {
+            return (0, 0);
+        }
+        let hash = self.hash(i);
+        let candidate = core::mem::replace(&mut self.head[hash], self.base + i + 1);
+        let Some(candidate) = candidate.checked_sub(self.base + 1) else {
+            return (0, 0);
+        };
+        let distance = i - candidate;
+        if distance > WINDOW_SIZE {
+            return (0, 0);
+        }
+        let max_len = (end - i).min(MAX_MATCH);
+        let len = self.buf[candidate..]
+            .iter()
+            .zip(&self.buf[i..i + max_len])
+            .take_while(|(a, b)| a == b)
+            .count();
+        (len, distance)
+    }
+
+    /// Writes bits in the order of the deflate stream, least significant bit first.
+    #[inline]
+    fn put_bits(&mut self, value: u32, n_bits: u32) {
+        self.bit_buf |= (value as u64) << self.bit_count;
+        self.bit_count += n_bits;
+        while self.bit_count >= 8 {
+            self.output.push(self.bit_buf as u8);
+            self.bit_buf >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which is stored most significant bit first.
+    #[inline]
+    fn put_code(&mut self, code: u32, n_bits: u32) {
+        self.put_bits(code.reverse_bits() >> (32 - n_bits), n_bits);
+    }
+
+    /// Writes a symbol of the literal/length alphabet with the fixed Huffman code.
+    fn put_symbol(&mut self, symbol: usize) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.put_code(0x30 + symbol, 8),
+            144..=255 => self.put_code(0x190 + symbol - 144, 9),
+            256..=279 => self.put_code(symbol - 256, 7),
+            _ => self.put_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn put_length(&mut self, len: usize) {
+        let index = LENGTH_BASE
+            .iter()
+            .rposition(|&base| base as usize <= len)
+            .unwrap_or_default();
+        self.put_symbol(257 + index);
+        self.put_bits(
+            (len - LENGTH_BASE[index] as usize) as u32,
+            LENGTH_EXTRA[index] as u32,
+        );
+    }
+
+    fn put_distance(&mut self, distance: usize) {
+        let index = DISTANCE_BASE
+            .iter()
+            .rposition(|&base| base as usize <= distance)
+            .unwrap_or_default();
+        self.put_code(index as u32, 5);
+        self.put_bits(
+            (distance - DISTANCE_BASE[index] as usize) as u32,
+            DISTANCE_EXTRA[index] as u32,
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Scanline filters
+//!
+//! The decoder reverses these filters internally. The forward filter is provided for encoders.
+
+#[cfg(feature = "alloc")]
+use crate::FilterType;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Average of the left and above bytes, rounded down
+#[inline]
+pub fn average(lhs: u8, rhs: u8) -> u8 {
+    let avg = (lhs as u16 + rhs as u16) >> 1;
+    avg as u8
+}
+
+/// Paeth predictor
+///
+/// Although the specification states that it is unsigned,
+/// here it is calculated as a signed integer because the decoding result differs when calculated without a sign.
+#[inline]
+pub fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
+    let a = left as i32;
+    let b = above as i32;
+    let c = upper_left as i32;
+    let p = a.wrapping_add(b).wrapping_sub(c);
+    let pa = p.abs_diff(a);
+    let pb = p.abs_diff(b);
+    let pc = p.abs_diff(c);
+    if pa <= pb && pa <= pc {
+        a as u8
+    } else if pb <= pc {
+        b as u8
+    } else {
+        c as u8
+    }
+}
+
+/// Applies the forward filter to a scanline and appends the filtered bytes to `output`.
+///
+/// `bpp` is the distance in bytes to the corresponding byte of the left pixel (at least 1),
+/// and `prev_line` is the original previous scanline, or all zeros for the first scanline.
+/// The filter type byte itself is not written.
+#[cfg(feature = "alloc")]
+pub fn filter_line(
+    filter_type: FilterType,
+    bpp: usize,
+    line: &[u8],
+    prev_line: &[u8],
+    output: &mut Vec<u8>,
+) {
+    for (i, (&x, &above)) in line.iter().zip(prev_line.iter()).enumerate() {
+        let (left, upper_left) = if i >= bpp {
+            (line[i - bpp], prev_line[i - bpp])
+        } else {
+            (0, 0)
+        };
+        let predictor = match filter_type {
+            FilterType::None => 0,
+            FilterType::Sub => left,
+            FilterType::Up => above,
+            FilterType::Average => average(left, above),
+            FilterType::Paeth => paeth(left, above, upper_left),
+        };
+        output.push(x.wrapping_sub(predictor));
+    }
+}
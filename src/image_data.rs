@@ -1,23 +1,262 @@
 use crate::*;
+#[cfg(feature = "alloc")]
 use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
-use core::ops::{Deref, DerefMut};
+#[cfg(feature = "alloc")]
+use color::RGBA8888;
+#[cfg(feature = "alloc")]
+use core::ops::{Deref, DerefMut, Index};
 
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
 pub struct ImageData {
     pub(crate) info: ImageInfo,
     pub(crate) palette: Vec<RGB888>,
+    /// Alpha table of index color format images from the tRNS chunk
+    pub(crate) transparency: Vec<u8>,
     pub(crate) data: Vec<u8>,
+    /// Full precision samples of 16-bit images, otherwise empty
+    pub(crate) data16: Vec<u16>,
+    /// Background color from the bKGD chunk
+    pub(crate) background: Option<RGBA8888>,
+}
+
+#[cfg(feature = "alloc")]
+impl core::fmt::Debug for ImageData {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // The image data can be huge, so only the lengths are shown
+        f.debug_struct("ImageData")
+            .field("info", &self.info)
+            .field("palette_len", &self.palette.len())
+            .field("data_len", &self.data.len())
+            .field("background", &self.background)
+            .finish()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImageInfo {
     pub width: u32,
     pub height: u32,
     pub bit_depth: BitDepth,
     pub image_type: ImageType,
+    /// The compression method declared in the IHDR chunk (currently always 0)
+    pub compression_method: u8,
+    /// The filter method declared in the IHDR chunk (currently always 0)
+    pub filter_method: u8,
+    /// The interlace method declared in the IHDR chunk (currently always 0)
+    pub interlace_method: u8,
+}
+
+impl ImageInfo {
+    /// Parses the data of the IHDR chunk.
+    ///
+    /// Returns an error if the values are invalid, or if an unsupported feature is required.
+    pub fn from_ihdr_data(data: &[u8; 13]) -> Result<ImageInfo, DecodeError> {
+        let info = Self::parse_ihdr(data)?;
+        info.validate()?;
+        Ok(info)
+    }
+
+    /// Parses the data of the IHDR chunk without checking that this crate can decode the image.
+    ///
+    /// Only the structure is checked: the width and height must be between 1 and 2^31-1,
+    /// and the bit depth and color type must be values defined by the specification.
+    /// Use [`Self::unsupported_features`] to find out why such an image cannot be decoded.
+    pub fn parse_ihdr(data: &[u8; 13]) -> Result<ImageInfo, DecodeError> {
+        let Some(bit_depth) = BitDepth::new(data[8]) else {
+            return Err(DecodeError::UnsupportedFormat);
+        };
+        let image_type = match data[9] {
+            0 => ImageType::Grayscale,
+            2 => ImageType::RGB,
+            3 => ImageType::Indexed,
+            4 => ImageType::GrayscaleAlpha,
+            6 => ImageType::RGBA,
+            _ => return Err(DecodeError::UnsupportedFormat),
+        };
+        let info = ImageInfo {
+            width: Be32(data[0..4].try_into().unwrap()).as_u32(),
+            height: Be32(data[4..8].try_into().unwrap()).as_u32(),
+            bit_depth,
+            image_type,
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        };
+        if !info.has_valid_size() {
+            return Err(DecodeError::InvalidData);
+        }
+        Ok(info)
+    }
+
+    /// Returns the data of the IHDR chunk describing the image information.
+    pub fn to_ihdr_data(&self) -> [u8; 13] {
+        let mut data = [0; 13];
+        data[0..4].copy_from_slice(&Be32::from_u32(self.width).0);
+        data[4..8].copy_from_slice(&Be32::from_u32(self.height).0);
+        data[8] = self.bit_depth as u8;
+        data[9] = self.raw_color_type();
+        data[10] = self.compression_method;
+        data[11] = self.filter_method;
+        data[12] = self.interlace_method;
+        data
+    }
+
+    /// Checks that the image information conforms to the specification and is supported by this crate.
+    ///
+    /// This applies the same rules as [`Self::from_ihdr_data`], for image information built by hand.
+    pub fn validate(&self) -> Result<(), DecodeError> {
+        if !self.has_valid_size() {
+            return Err(DecodeError::InvalidData);
+        }
+        if cfg!(target_pointer_width = "32")
+            && (self.width.saturating_mul(self.height) > 0x1000_0000)
+        {
+            // maybe overflow
+            return Err(DecodeError::UnsupportedFormat);
+        }
+        if !self.image_type.is_valid_bit_depth(self.bit_depth) {
+            return Err(DecodeError::InvalidBitDepth {
+                color_type: self.raw_color_type(),
+                bit_depth: self.bit_depth as u8,
+            });
+        }
+        // the sizes of the image stream and the decoded data must be addressable
+        self.image_stream_len()?;
+        self.checked_decoded_len()?;
+        // currently not supported
+        if self.compression_method != 0 {
+            return Err(DecodeError::UnsupportedCompressionMethod(
+                self.compression_method,
+            ));
+        }
+        if self.filter_method != 0 {
+            return Err(DecodeError::UnsupportedFilterMethod(self.filter_method));
+        }
+        if self.interlace_method != 0 {
+            return Err(DecodeError::UnsupportedInterlaceMethod(
+                self.interlace_method,
+            ));
+        }
+        Ok(())
+    }
+
+    /// The PNG specification limits the width and height to 1..=2^31-1.
+    #[inline]
+    fn has_valid_size(&self) -> bool {
+        (1..=i32::MAX as u32).contains(&self.width) && (1..=i32::MAX as u32).contains(&self.height)
+    }
+
+    /// Returns the size in bytes of the PNG image stream, including the filter type bytes.
+    ///
+    /// Returns an error if the size overflows.
+    pub(crate) fn image_stream_len(&self) -> Result<usize, DecodeError> {
+        (self.width as usize)
+            .checked_mul(self.image_type.n_channels() * self.bit_depth as usize)
+            .map(|bits| bits.div_ceil(8) + 1)
+            .and_then(|stride| stride.checked_mul(self.height as usize))
+            .filter(|&len| len <= isize::MAX as usize)
+            .ok_or(DecodeError::UnsupportedFormat)
+    }
+
+    /// Returns the size in bytes of the decoded image data, like [`Self::decoded_len`].
+    ///
+    /// Returns an error if the size overflows.
+    pub(crate) fn checked_decoded_len(&self) -> Result<usize, DecodeError> {
+        (self.width as usize)
+            .checked_mul(self.height as usize)
+            .and_then(|len| len.checked_mul(self.image_type.n_channels()))
+            .filter(|&len| len <= isize::MAX as usize)
+            .ok_or(DecodeError::UnsupportedFormat)
+    }
+
+    /// Returns the names of the features declared by the image information that this crate cannot decode.
+    ///
+    /// An empty list means that the image is decodable as far as the header is concerned.
+    #[cfg(feature = "alloc")]
+    pub fn unsupported_features(&self) -> Vec<&'static str> {
+        let mut features = Vec::new();
+        if !self.image_type.is_valid_bit_depth(self.bit_depth) {
+            features.push("bit depth for the color type");
+        }
+        if self.compression_method != 0 {
+            features.push("compression method");
+        }
+        if self.filter_method != 0 {
+            features.push("filter method");
+        }
+        match self.interlace_method {
+            0 => {}
+            1 => features.push("Adam7 interlace"),
+            _ => features.push("interlace method"),
+        }
+        features
+    }
+
+    /// Returns the number of bytes in a scanline of the PNG image stream, excluding the filter type byte.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bit depth is not allowed for the image type.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        assert!(
+            self.image_type.is_valid_bit_depth(self.bit_depth),
+            "{} is not allowed for {}",
+            self.bit_depth,
+            self.image_type
+        );
+        (self.width as usize * self.image_type.n_channels() * self.bit_depth as usize).div_ceil(8)
+    }
+
+    /// Returns the size in bytes of the decoded image data.
+    ///
+    /// Each sample occupies one byte regardless of the bit depth.
+    /// 16-bit samples are reduced to their most significant byte.
+    #[inline]
+    pub fn decoded_len(&self) -> usize {
+        self.width as usize * self.height as usize * self.image_type.n_channels()
+    }
+
+    /// Returns the bit depth byte as declared in the IHDR chunk.
+    #[inline]
+    pub fn raw_bit_depth(&self) -> u8 {
+        self.bit_depth as u8
+    }
+
+    /// Returns the color type byte as declared in the IHDR chunk.
+    #[inline]
+    pub fn raw_color_type(&self) -> u8 {
+        self.image_type.color_type()
+    }
+
+    /// Returns the ratio of width to height.
+    #[inline]
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    #[inline]
+    pub fn is_landscape(&self) -> bool {
+        self.width > self.height
+    }
+
+    #[inline]
+    pub fn is_portrait(&self) -> bool {
+        self.width < self.height
+    }
+
+    #[inline]
+    pub fn is_square(&self) -> bool {
+        self.width == self.height
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ImageType {
     Grayscale,
     GrayscaleAlpha,
@@ -26,6 +265,18 @@ pub enum ImageType {
     Indexed,
 }
 
+impl core::fmt::Display for ImageType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Grayscale => "Grayscale",
+            Self::GrayscaleAlpha => "Grayscale with alpha",
+            Self::RGB => "Truecolor",
+            Self::RGBA => "Truecolor with alpha",
+            Self::Indexed => "Indexed-color",
+        })
+    }
+}
+
 impl ImageType {
     #[inline]
     pub fn n_channels(&self) -> usize {
@@ -38,6 +289,56 @@ impl ImageType {
         }
     }
 
+    /// Returns the color type byte of the IHDR chunk for this image type.
+    #[inline]
+    pub fn color_type(&self) -> u8 {
+        match self {
+            ImageType::Grayscale => 0,
+            ImageType::RGB => 2,
+            ImageType::Indexed => 3,
+            ImageType::GrayscaleAlpha => 4,
+            ImageType::RGBA => 6,
+        }
+    }
+
+    /// Returns `true` if the bit depth is allowed for this image type.
+    ///
+    /// Sub-8-bit depths are only allowed for single channel images, and 16-bit depth is not allowed for index color.
+    #[inline]
+    pub fn is_valid_bit_depth(&self, bit_depth: BitDepth) -> bool {
+        match self {
+            ImageType::Grayscale => true,
+            ImageType::Indexed => bit_depth <= BitDepth::Bpp8,
+            ImageType::GrayscaleAlpha | ImageType::RGB | ImageType::RGBA => {
+                bit_depth >= BitDepth::Bpp8
+            }
+        }
+    }
+
+    /// Returns the names of the stored channels, in sample order.
+    ///
+    /// The length is the same as [`Self::n_channels`].
+    #[inline]
+    pub fn channel_names(&self) -> &'static [&'static str] {
+        match self {
+            ImageType::Grayscale => &["Gray"],
+            ImageType::GrayscaleAlpha => &["Gray", "A"],
+            ImageType::RGB => &["R", "G", "B"],
+            ImageType::RGBA => &["R", "G", "B", "A"],
+            ImageType::Indexed => &["Index"],
+        }
+    }
+
+    /// Returns the number of channels after the palette is expanded.
+    ///
+    /// Unlike [`Self::n_channels`], which is the number of samples stored per pixel,
+    /// index color format images count as color channels here.
+    #[inline]
+    pub fn output_channels(&self, with_alpha: bool) -> usize {
+        let color_channels = if self.is_gray_scale() { 1 } else { 3 };
+        color_channels + with_alpha as usize
+    }
+
     #[inline]
     pub fn has_alpha(&self) -> bool {
         matches!(self, Self::GrayscaleAlpha | Self::RGBA)
@@ -53,6 +354,7 @@ impl ImageType {
         !self.is_gray_scale()
     }
 
+    #[cfg(feature = "alloc")]
     #[inline]
     pub fn for_each<F, E>(&self, slice: &[u8], palette: &[RGB888], mut kernel: F) -> Result<(), E>
     where
@@ -64,10 +366,28 @@ impl ImageType {
         Ok(())
     }
 
+    #[cfg(feature = "alloc")]
+    /// Returns an iterator over the colors of the pixels in the slice.
+    ///
+    /// For index color format images, indices outside the palette are treated as black.
+    #[inline]
     pub fn iter<'a>(
         &self,
         slice: &'a [u8],
         palette: &'a [RGB888],
+    ) -> Box<dyn Iterator<Item = color::RGBA8888> + 'a> {
+        self.iter_with_transparency(slice, palette, &[])
+    }
+
+    /// Same as [`Self::iter`], but index color format images take alpha values from the tRNS alpha table.
+    ///
+    /// Missing entries in the alpha table are treated as opaque.
+    #[cfg(feature = "alloc")]
+    pub fn iter_with_transparency<'a>(
+        &self,
+        slice: &'a [u8],
+        palette: &'a [RGB888],
+        transparency: &'a [u8],
     ) -> Box<dyn Iterator<Item = color::RGBA8888> + 'a> {
         use color::RGBA8888;
         match self {
@@ -87,15 +407,63 @@ impl ImageType {
                     .chunks_exact(4)
                     .map(|chunk| RGBA8888::from_rgba(chunk[0], chunk[1], chunk[2], chunk[3])),
             ),
-            Self::Indexed => Box::new(
+            Self::Indexed => Box::new(slice.iter().map(|&index| {
+                let color = palette
+                    .get(index as usize)
+                    .copied()
+                    .unwrap_or(RGB888::new(0, 0, 0));
+                let alpha = transparency.get(index as usize).copied().unwrap_or(0xFF);
+                RGBA8888::from_rgba(color.r, color.g, color.b, alpha)
+            })),
+        }
+    }
+
+    /// Same as [`Self::iter`], but yields colors without alpha, so no alpha value is computed.
+    #[cfg(feature = "alloc")]
+    pub fn iter_rgb<'a>(
+        &self,
+        slice: &'a [u8],
+        palette: &'a [RGB888],
+    ) -> Box<dyn Iterator<Item = RGB888> + 'a> {
+        match self {
+            Self::Grayscale => Box::new(slice.iter().map(|&gray| RGB888::from_gray(gray))),
+            Self::GrayscaleAlpha => Box::new(
+                slice
+                    .chunks_exact(2)
+                    .map(|chunk| RGB888::from_gray(chunk[0])),
+            ),
+            Self::RGB => Box::new(
                 slice
-                    .iter()
-                    .map(|index| palette[*index as usize].into_rgba()),
+                    .chunks_exact(3)
+                    .map(|chunk| RGB888::new(chunk[0], chunk[1], chunk[2])),
             ),
+            Self::RGBA => Box::new(
+                slice
+                    .chunks_exact(4)
+                    .map(|chunk| RGB888::new(chunk[0], chunk[1], chunk[2])),
+            ),
+            Self::Indexed => Box::new(slice.iter().map(|&index| {
+                palette
+                    .get(index as usize)
+                    .copied()
+                    .unwrap_or(RGB888::new(0, 0, 0))
+            })),
         }
     }
 
+    #[cfg(feature = "alloc")]
+    #[inline]
     pub fn to_rgba_bytes<'a>(&self, input: &'a [u8], palette: &[RGB888]) -> RgbaBytes<'a> {
+        self.to_rgba_bytes_with_transparency(input, palette, &[])
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn to_rgba_bytes_with_transparency<'a>(
+        &self,
+        input: &'a [u8],
+        palette: &[RGB888],
+        transparency: &[u8],
+    ) -> RgbaBytes<'a> {
         match self {
             Self::RGBA => {
                 // No conversion needed
@@ -104,7 +472,7 @@ impl ImageType {
             _ => {
                 // Convert to RGBA
                 let mut output = Vec::with_capacity(input.len() / self.n_channels() * 4);
-                for rgba in self.iter(input, palette) {
+                for rgba in self.iter_with_transparency(input, palette, transparency) {
                     output.push(rgba.r());
                     output.push(rgba.g());
                     output.push(rgba.b());
@@ -115,6 +483,7 @@ impl ImageType {
         }
     }
 
+    #[cfg(feature = "alloc")]
     pub fn to_rgb_bytes<'a>(&self, input: &'a [u8], palette: &[RGB888]) -> RgbBytes<'a> {
         match self {
             Self::RGB => {
@@ -124,17 +493,34 @@ impl ImageType {
             _ => {
                 // Convert to RGB
                 let mut output = Vec::with_capacity(input.len() / self.n_channels() * 3);
-                for rgba in self.iter(input, palette) {
-                    output.push(rgba.r());
-                    output.push(rgba.g());
-                    output.push(rgba.b());
+                for rgb in self.iter_rgb(input, palette) {
+                    output.extend_from_slice(&[rgb.r, rgb.g, rgb.b]);
                 }
                 RgbBytes(Cow::Owned(output))
             }
         }
     }
+
+    #[cfg(feature = "alloc")]
+    pub fn to_gray_bytes<'a>(&self, input: &'a [u8], palette: &[RGB888]) -> Cow<'a, [u8]> {
+        match self {
+            Self::Grayscale => {
+                // No conversion needed
+                Cow::Borrowed(input)
+            }
+            _ => {
+                // Convert to grayscale by luminance
+                let output = self
+                    .iter(input, palette)
+                    .map(|rgba| rgba.luminance())
+                    .collect();
+                Cow::Owned(output)
+            }
+        }
+    }
 }
 
+#[cfg(feature = "alloc")]
 impl ImageData {
     #[inline]
     pub fn info(&self) -> &ImageInfo {
@@ -153,6 +539,43 @@ impl ImageData {
         }
     }
 
+    /// For truecolor images, the optional palette from the PLTE chunk is returned.
+    ///
+    /// The encoder suggests this palette for quantizing the image to index colors.
+    /// Returns `None` if there is no PLTE chunk or for other image types.
+    #[inline]
+    pub fn suggested_palette(&self) -> Option<&[RGB888]> {
+        if self.info.image_type.is_color()
+            && self.info.image_type != ImageType::Indexed
+            && !self.palette.is_empty()
+        {
+            Some(&self.palette)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the background color from the bKGD chunk, if any.
+    ///
+    /// The pixels are not composited onto it, it is only a hint for presentation.
+    /// Samples are converted to 8 bits, and the color is always opaque.
+    #[inline]
+    pub fn background(&self) -> Option<RGBA8888> {
+        self.background
+    }
+
+    /// For index color format images, the alpha table from the tRNS chunk is returned.
+    ///
+    /// The table may be shorter than the palette, in which case the remaining entries are opaque.
+    #[inline]
+    pub fn transparency(&self) -> Option<&[u8]> {
+        if self.info.image_type == ImageType::Indexed && !self.transparency.is_empty() {
+            Some(&self.transparency)
+        } else {
+            None
+        }
+    }
+
     /// Return image data in raw format.
     ///
     /// If the format is different from your expectations, data conversion is required.
@@ -161,14 +584,82 @@ impl ImageData {
         &self.data
     }
 
+    /// For 16-bit images, the samples are returned with full precision.
+    ///
+    /// The layout is the same as [`Self::raw_data`], which only keeps the most significant byte of each sample.
+    /// Returns `None` for other bit depths.
+    #[inline]
+    pub fn raw_data_u16(&self) -> Option<&[u16]> {
+        if self.info.bit_depth == BitDepth::Bpp16 {
+            Some(&self.data16)
+        } else {
+            None
+        }
+    }
+
+    /// For index color format images, the palette indices are returned, one byte per pixel.
+    ///
+    /// Returns `None` for other image types.
+    #[inline]
+    pub fn indices(&self) -> Option<&[u8]> {
+        if self.info.image_type == ImageType::Indexed {
+            Some(&self.data)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a scanline of the raw data, or `None` if `y` is out of range.
+    ///
+    /// Each sample occupies one byte, even for images with bit depth less than 8.
+    #[inline]
+    pub fn row(&self, y: u32) -> Option<&[u8]> {
+        if y >= self.info.height {
+            return None;
+        }
+        let row_len = self.row_len();
+        self.data
+            .get(y as usize * row_len..(y as usize + 1) * row_len)
+    }
+
+    /// Returns the color of the pixel at `(x, y)`, or `None` if the coordinates are out of range.
+    ///
+    /// The color is converted in the same way as [`Self::to_rgba_bytes`].
+    /// Use indexing to get the raw samples instead.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<RGBA8888> {
+        if x >= self.info.width || y >= self.info.height {
+            return None;
+        }
+        self.info
+            .image_type
+            .iter_with_transparency(&self[(x, y)], &self.palette, &self.transparency)
+            .next()
+    }
+
+    /// Returns an iterator over the scanlines of the raw data, from top to bottom.
+    ///
+    /// Each item has the same layout as [`Self::row`].
+    #[inline]
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.data.chunks_exact(self.row_len())
+    }
+
+    /// Number of bytes in a scanline of the raw data
+    #[inline]
+    fn row_len(&self) -> usize {
+        self.info.width as usize * self.info.image_type.n_channels()
+    }
+
     /// Return image data in RGBA format.
     ///
     /// If another format is used, it will be converted.
     #[inline]
     pub fn to_rgba_bytes<'a>(&'a self) -> RgbaBytes<'a> {
-        self.info
-            .image_type
-            .to_rgba_bytes(self.data.as_slice(), &self.palette)
+        self.info.image_type.to_rgba_bytes_with_transparency(
+            self.data.as_slice(),
+            &self.palette,
+            &self.transparency,
+        )
     }
 
     /// Return image data in RGB format.
@@ -180,10 +671,711 @@ impl ImageData {
             .image_type
             .to_rgb_bytes(self.data.as_slice(), &self.palette)
     }
+
+    /// Return image data in 8-bit grayscale format.
+    ///
+    /// Grayscale images are borrowed as is, other formats are converted by luminance.
+    #[inline]
+    pub fn to_gray_bytes(&self) -> Cow<'_, [u8]> {
+        self.info
+            .image_type
+            .to_gray_bytes(self.data.as_slice(), &self.palette)
+    }
+
+    /// Return image data in the specified format.
+    ///
+    /// The raw data is borrowed if it is already in that format, otherwise it will be converted.
+    pub fn to_format(&self, format: OutputFormat) -> Cow<'_, [u8]> {
+        match format {
+            OutputFormat::Rgb8 => self.to_rgb_bytes().0,
+            OutputFormat::Rgba8 => self.to_rgba_bytes().0,
+            OutputFormat::Bgra8 => {
+                let mut output = self.to_rgba_bytes().0.into_owned();
+                for pixel in output.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+                Cow::Owned(output)
+            }
+            OutputFormat::Gray8 => self.to_gray_bytes(),
+            OutputFormat::GrayAlpha8 => match self.info.image_type {
+                ImageType::GrayscaleAlpha => Cow::Borrowed(&self.data),
+                image_type => {
+                    let mut output =
+                        Vec::with_capacity(self.data.len() / image_type.n_channels() * 2);
+                    for rgba in image_type.iter_with_transparency(
+                        &self.data,
+                        &self.palette,
+                        &self.transparency,
+                    ) {
+                        output.push(rgba.luminance());
+                        output.push(rgba.a());
+                    }
+                    Cow::Owned(output)
+                }
+            },
+        }
+    }
+
+    /// For grayscale images, the raw data is borrowed as is, without conversion.
+    ///
+    /// Returns `None` for other image types, see [`Self::to_gray_bytes`] to convert them.
+    #[inline]
+    pub fn as_gray(&self) -> Option<&[u8]> {
+        if self.info.image_type == ImageType::Grayscale {
+            Some(&self.data)
+        } else {
+            None
+        }
+    }
+
+    /// Return image data packed into RGB565, one `u16` per pixel.
+    ///
+    /// Red occupies the most significant 5 bits and blue the least significant 5 bits.
+    /// Alpha is ignored.
+    pub fn to_rgb565(&self) -> Vec<u16> {
+        self.info
+            .image_type
+            .iter_rgb(&self.data, &self.palette)
+            .map(|color| color.to_rgb565())
+            .collect()
+    }
+
+    /// Return image data packed into RGB565, two bytes per pixel in big endian.
+    ///
+    /// This is the order in which most SPI displays receive pixels.
+    pub fn to_rgb565_be_bytes(&self) -> Vec<u8> {
+        self.info
+            .image_type
+            .iter_rgb(&self.data, &self.palette)
+            .flat_map(|color| color.to_rgb565().to_be_bytes())
+            .collect()
+    }
+
+    /// Return image data packed into RGB565, two bytes per pixel in little endian.
+    pub fn to_rgb565_le_bytes(&self) -> Vec<u8> {
+        self.info
+            .image_type
+            .iter_rgb(&self.data, &self.palette)
+            .flat_map(|color| color.to_rgb565().to_le_bytes())
+            .collect()
+    }
+
+    /// Return image data in RGB format, applying ordered dithering when reducing 16-bit samples.
+    ///
+    /// Images with 8-bit or less depth are converted in the same way as [`Self::to_rgb_bytes`].
+    pub fn to_rgb_bytes_dithered<'a>(&'a self) -> RgbBytes<'a> {
+        if self.info.bit_depth != BitDepth::Bpp16 {
+            return self.to_rgb_bytes();
+        }
+        // 4x4 Bayer matrix
+        const BAYER: [[u32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+        let width = self.info.width as usize;
+        let n_channels = self.info.image_type.n_channels();
+        let mut output = Vec::with_capacity(self.data16.len() / n_channels * 3);
+        for (i, pixel) in self.data16.chunks_exact(n_channels).enumerate() {
+            let threshold = BAYER[(i / width) & 3][(i % width) & 3];
+            let dither = |sample: u16| {
+                let scaled = sample as u32 * 255;
+                let base = scaled / 65535;
+                let fraction = scaled % 65535;
+                // round up if the fraction exceeds the threshold (threshold + 0.5) / 16
+                if fraction * 32 > (threshold * 2 + 1) * 65535 {
+                    base as u8 + 1
+                } else {
+                    base as u8
+                }
+            };
+            if self.info.image_type.is_gray_scale() {
+                let gray = dither(pixel[0]);
+                output.extend_from_slice(&[gray, gray, gray]);
+            } else {
+                output.extend_from_slice(&[dither(pixel[0]), dither(pixel[1]), dither(pixel[2])]);
+            }
+        }
+        RgbBytes(Cow::Owned(output))
+    }
+
+    /// Return image data in RGBA format with each sample normalized to the range `0.0..=1.0`.
+    ///
+    /// 16-bit images keep their full precision.
+    pub fn to_rgba_f32(&self) -> Vec<f32> {
+        let n_pixels = self.info.width as usize * self.info.height as usize;
+        let mut output = Vec::with_capacity(n_pixels * 4);
+        if self.data16.is_empty() {
+            for color in self.info.image_type.iter_with_transparency(
+                &self.data,
+                &self.palette,
+                &self.transparency,
+            ) {
+                for sample in color.components().into_array() {
+                    output.push(sample as f32 / 255.0);
+                }
+            }
+        } else {
+            let n_channels = self.info.image_type.n_channels();
+            let normalize = |sample: u16| sample as f32 / 65535.0;
+            for pixel in self.data16.chunks_exact(n_channels) {
+                let (r, g, b) = if self.info.image_type.is_gray_scale() {
+                    (pixel[0], pixel[0], pixel[0])
+                } else {
+                    (pixel[0], pixel[1], pixel[2])
+                };
+                let a = if self.info.image_type.has_alpha() {
+                    pixel[n_channels - 1]
+                } else {
+                    0xFFFF
+                };
+                output.extend_from_slice(&[normalize(r), normalize(g), normalize(b), normalize(a)]);
+            }
+        }
+        output
+    }
+
+    /// Converts the image into RGBA format.
+    ///
+    /// Unlike [`Self::to_rgba_bytes`], the result is an `ImageData` whose image type is `RGBA`.
+    pub fn into_rgba(self) -> ImageData {
+        if self.info.image_type == ImageType::RGBA {
+            return self;
+        }
+        let data = self.to_rgba_bytes().to_vec();
+        ImageData {
+            info: ImageInfo {
+                bit_depth: BitDepth::Bpp8,
+                image_type: ImageType::RGBA,
+                ..self.info
+            },
+            palette: Vec::new(),
+            transparency: Vec::new(),
+            background: self.background,
+            data,
+            data16: Vec::new(),
+        }
+    }
+
+    /// Returns a new image resized to the specified size with bilinear interpolation.
+    ///
+    /// The pixels are converted to RGBA first and interpolated per channel without premultiplying alpha,
+    /// so the result is always an 8-bit RGBA image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` or `height` is zero.
+    pub fn resize_bilinear(&self, width: u32, height: u32) -> ImageData {
+        assert!(width > 0 && height > 0, "the size must not be zero");
+        let src = self.to_rgba_bytes();
+        let src_width = self.info.width as usize;
+        // source coordinates of the pixel centers in 1/256 pixels, clamped to the edges
+        let positions = |size: u32, src_size: u32| {
+            let max = (src_size as i64 - 1) * 256;
+            (0..size as i64).map(move |v| {
+                let pos =
+                    ((2 * v + 1) * src_size as i64 * 256 / (2 * size as i64) - 128).clamp(0, max);
+                let index = (pos >> 8) as usize;
+                let next = (index + 1).min(src_size as usize - 1);
+                (index, next, (pos & 0xFF) as u32)
+            })
+        };
+        let xs = positions(width, self.info.width).collect::<Vec<_>>();
+        let mut data = Vec::with_capacity(width as usize * height as usize * 4);
+        for (y0, y1, fy) in positions(height, self.info.height) {
+            for &(x0, x1, fx) in xs.iter() {
+                let weights = [
+                    (y0 * src_width + x0, (256 - fx) * (256 - fy)),
+                    (y0 * src_width + x1, fx * (256 - fy)),
+                    (y1 * src_width + x0, (256 - fx) * fy),
+                    (y1 * src_width + x1, fx * fy),
+                ];
+                for channel in 0..4 {
+                    let sum = weights
+                        .iter()
+                        .map(|&(pixel, weight)| src[pixel * 4 + channel] as u32 * weight)
+                        .sum::<u32>();
+                    data.push(((sum + 0x8000) >> 16) as u8);
+                }
+            }
+        }
+        ImageData {
+            info: ImageInfo {
+                width,
+                height,
+                bit_depth: BitDepth::Bpp8,
+                image_type: ImageType::RGBA,
+                ..self.info
+            },
+            palette: Vec::new(),
+            transparency: Vec::new(),
+            background: self.background,
+            data,
+            data16: Vec::new(),
+        }
+    }
+
+    /// Swaps the R and B channels in place, converting between RGB and BGR order.
+    ///
+    /// For index color format images, the palette entries are swapped instead. Grayscale images are not changed.
+    pub fn swap_rb(&mut self) {
+        match self.info.image_type {
+            ImageType::Grayscale | ImageType::GrayscaleAlpha => {}
+            ImageType::Indexed => {
+                for color in self.palette.iter_mut() {
+                    core::mem::swap(&mut color.r, &mut color.b);
+                }
+            }
+            ImageType::RGB | ImageType::RGBA => {
+                let n_channels = self.info.image_type.n_channels();
+                for pixel in self.data.chunks_exact_mut(n_channels) {
+                    pixel.swap(0, 2);
+                }
+                for pixel in self.data16.chunks_exact_mut(n_channels) {
+                    pixel.swap(0, 2);
+                }
+            }
+        }
+    }
+
+    /// Rewrites every pixel in place through the specified function.
+    ///
+    /// For index color format images, the palette is rewritten instead of the indices.
+    /// For grayscale images, the luminance of the result is stored.
+    /// For 16-bit images, the full precision samples are rewritten as well,
+    /// and only the samples the function leaves unchanged keep their lower 8 bits.
+    pub fn map_pixels<F>(&mut self, mut f: F)
+    where
+        F: FnMut(RGBA8888) -> RGBA8888,
+    {
+        let image_type = self.info.image_type;
+        if image_type == ImageType::Indexed {
+            for (index, color) in self.palette.iter_mut().enumerate() {
+                let alpha = self.transparency.get(index).copied().unwrap_or(0xFF);
+                let result = f(RGBA8888::from_rgba(color.r, color.g, color.b, alpha));
+                *color = result.to_rgb();
+                if result.a() != alpha {
+                    if self.transparency.len() <= index {
+                        self.transparency.resize(index + 1, 0xFF);
+                    }
+                    self.transparency[index] = result.a();
+                }
+            }
+            return;
+        }
+
+        let n_channels = image_type.n_channels();
+        let mut data16 = self.data16.chunks_exact_mut(n_channels);
+        for pixel in self.data.chunks_exact_mut(n_channels) {
+            let color = f(match image_type {
+                ImageType::Grayscale => RGBA8888::from_gray(pixel[0]),
+                ImageType::GrayscaleAlpha => RGBA8888::from_gray_alpha(pixel[0], pixel[1]),
+                ImageType::RGB => RGBA8888::from_rgb(pixel[0], pixel[1], pixel[2]),
+                _ => RGBA8888::from_rgba(pixel[0], pixel[1], pixel[2], pixel[3]),
+            });
+            let values = match image_type {
+                ImageType::Grayscale | ImageType::GrayscaleAlpha => {
+                    [color.luminance(), color.a(), 0, 0]
+                }
+                _ => [color.r(), color.g(), color.b(), color.a()],
+            };
+            let mut pixel16 = data16.next();
+            for (channel, (sample, value)) in pixel.iter_mut().zip(values).enumerate() {
+                if *sample != value {
+                    *sample = value;
+                    if let Some(pixel16) = pixel16.as_mut() {
+                        pixel16[channel] = value as u16 * 0x101;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replaces every pixel of the color `from` with the color `to`, such as for chroma keying.
+    ///
+    /// For index color format images, the matching palette entries are rewritten.
+    /// Images without an alpha channel cannot become transparent, except for index color format images.
+    #[inline]
+    pub fn replace_color(&mut self, from: RGBA8888, to: RGBA8888) {
+        self.map_pixels(|color| if color == from { to } else { color });
+    }
+
+    /// Returns a new image cut out from the specified rectangle.
+    ///
+    /// The image type and palette are preserved.
+    /// Returns an error if the rectangle is empty or out of bounds.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Result<ImageData, DecodeError> {
+        let right = x.checked_add(width).ok_or(DecodeError::InvalidData)?;
+        let bottom = y.checked_add(height).ok_or(DecodeError::InvalidData)?;
+        if width == 0 || height == 0 || right > self.info.width || bottom > self.info.height {
+            return Err(DecodeError::InvalidData);
+        }
+        let n_channels = self.info.image_type.n_channels();
+        let src_stride = self.row_len();
+        let dst_stride = width as usize * n_channels;
+        let left = x as usize * n_channels;
+        let mut data = Vec::with_capacity(dst_stride * height as usize);
+        for line in self
+            .data
+            .chunks_exact(src_stride)
+            .skip(y as usize)
+            .take(height as usize)
+        {
+            data.extend_from_slice(&line[left..left + dst_stride]);
+        }
+        let mut data16 = Vec::new();
+        for line in self
+            .data16
+            .chunks_exact(src_stride)
+            .skip(y as usize)
+            .take(height as usize)
+        {
+            data16.extend_from_slice(&line[left..left + dst_stride]);
+        }
+        Ok(ImageData {
+            info: ImageInfo {
+                width,
+                height,
+                ..self.info
+            },
+            palette: self.palette.clone(),
+            transparency: self.transparency.clone(),
+            background: self.background,
+            data,
+            data16,
+        })
+    }
+
+    /// Returns a new image rotated 90 degrees clockwise.
+    ///
+    /// The width and height are swapped, and the image type, bit depth and palette are preserved.
+    #[inline]
+    pub fn rotate90(&self) -> ImageData {
+        let height = self.info.height;
+        self.transform(self.info.height, self.info.width, |x, y| {
+            (y, height - 1 - x)
+        })
+    }
+
+    /// Returns a new image rotated 180 degrees.
+    ///
+    /// The image type, bit depth and palette are preserved.
+    #[inline]
+    pub fn rotate180(&self) -> ImageData {
+        let (width, height) = (self.info.width, self.info.height);
+        self.transform(width, height, |x, y| (width - 1 - x, height - 1 - y))
+    }
+
+    /// Returns a new image rotated 270 degrees clockwise, that is, 90 degrees counterclockwise.
+    ///
+    /// The width and height are swapped, and the image type, bit depth and palette are preserved.
+    #[inline]
+    pub fn rotate270(&self) -> ImageData {
+        let width = self.info.width;
+        self.transform(self.info.height, self.info.width, |x, y| (width - 1 - y, x))
+    }
+
+    /// Returns a new image of the specified size, where each pixel is copied from
+    /// the source coordinates returned by `source` for the destination coordinates.
+    fn transform<F>(&self, width: u32, height: u32, source: F) -> ImageData
+    where
+        F: Fn(u32, u32) -> (u32, u32),
+    {
+        let n_channels = self.info.image_type.n_channels();
+        let src_stride = self.row_len();
+        let len = width as usize * height as usize * n_channels;
+        let mut data = Vec::with_capacity(len);
+        let mut data16 = Vec::with_capacity(if self.data16.is_empty() { 0 } else { len });
+        for y in 0..height {
+            for x in 0..width {
+                let (src_x, src_y) = source(x, y);
+                let offset = src_y as usize * src_stride + src_x as usize * n_channels;
+                data.extend_from_slice(&self.data[offset..offset + n_channels]);
+                if !self.data16.is_empty() {
+                    data16.extend_from_slice(&self.data16[offset..offset + n_channels]);
+                }
+            }
+        }
+        ImageData {
+            info: ImageInfo {
+                width,
+                height,
+                ..self.info
+            },
+            palette: self.palette.clone(),
+            transparency: self.transparency.clone(),
+            background: self.background,
+            data,
+            data16,
+        }
+    }
+
+    /// Returns `true` if every pixel is fully opaque.
+    ///
+    /// Images without an alpha channel are always opaque,
+    /// except for index color format images whose tRNS alpha table makes a used entry transparent.
+    pub fn is_opaque(&self) -> bool {
+        match self.info.image_type {
+            ImageType::Grayscale | ImageType::RGB => true,
+            ImageType::Indexed => self.data.iter().all(|&index| {
+                self.transparency
+                    .get(index as usize)
+                    .is_none_or(|&a| a == 0xFF)
+            }),
+            ImageType::GrayscaleAlpha | ImageType::RGBA => {
+                let n_channels = self.info.image_type.n_channels();
+                if self.data16.is_empty() {
+                    self.data
+                        .chunks_exact(n_channels)
+                        .all(|pixel| pixel[n_channels - 1] == 0xFF)
+                } else {
+                    self.data16
+                        .chunks_exact(n_channels)
+                        .all(|pixel| pixel[n_channels - 1] == 0xFFFF)
+                }
+            }
+        }
+    }
+
+    /// For grayscale with alpha images, returns the gray and alpha planes separately.
+    pub fn gray_alpha_planes(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        if self.info.image_type != ImageType::GrayscaleAlpha {
+            return None;
+        }
+        let len = self.data.len() / 2;
+        let mut gray = Vec::with_capacity(len);
+        let mut alpha = Vec::with_capacity(len);
+        for chunk in self.data.chunks_exact(2) {
+            gray.push(chunk[0]);
+            alpha.push(chunk[1]);
+        }
+        Some((gray, alpha))
+    }
+
+    /// Returns the alpha value of each pixel as a mask, one byte per pixel.
+    ///
+    /// Index color format images take the values from the tRNS alpha table.
+    /// Returns `None` for images without alpha.
+    pub fn alpha_mask(&self) -> Option<Vec<u8>> {
+        match self.info.image_type {
+            ImageType::GrayscaleAlpha => Some(self.data.chunks_exact(2).map(|v| v[1]).collect()),
+            ImageType::RGBA => Some(self.data.chunks_exact(4).map(|v| v[3]).collect()),
+            ImageType::Indexed if !self.transparency.is_empty() => Some(
+                self.data
+                    .iter()
+                    .map(|&index| {
+                        self.transparency
+                            .get(index as usize)
+                            .copied()
+                            .unwrap_or(0xFF)
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// For grayscale with alpha images, composites the image onto a checkerboard pattern and returns RGB bytes.
+    ///
+    /// The checkerboard alternates between white and light gray cells of `cell` pixels,
+    /// starting with white at the top left, as shown by image editors for transparent areas.
+    pub fn to_gray_over_checkerboard(&self, cell: u32) -> Option<Vec<u8>> {
+        if self.info.image_type != ImageType::GrayscaleAlpha {
+            return None;
+        }
+        const LIGHT: u8 = 0xFF;
+        const DARK: u8 = 0xCC;
+        let cell = cell.max(1);
+        let width = self.info.width;
+        let mut output = Vec::with_capacity(self.data.len() / 2 * 3);
+        for (i, pixel) in self.data.chunks_exact(2).enumerate() {
+            let x = i as u32 % width;
+            let y = i as u32 / width;
+            let background = if (x / cell + y / cell).is_multiple_of(2) {
+                LIGHT
+            } else {
+                DARK
+            };
+            let gray = RGBA8888::from_gray_alpha(pixel[0], pixel[1])
+                .blend_over(RGBA8888::from_gray(background))
+                .r();
+            output.extend_from_slice(&[gray, gray, gray]);
+        }
+        Some(output)
+    }
+
+    /// Maps each pixel to the index of the nearest color in the specified palette.
+    ///
+    /// The distance is the squared Euclidean distance in RGB, and alpha is ignored.
+    /// On a tie, the lower index wins. Only the first 256 entries of the palette are used,
+    /// and an empty palette maps every pixel to index 0.
+    pub fn quantize_to_palette(&self, palette: &[RGB888]) -> Vec<u8> {
+        let palette = &palette[..palette.len().min(256)];
+        self.info
+            .image_type
+            .iter(&self.data, &self.palette)
+            .map(|color| {
+                let distance = |entry: &RGB888| {
+                    let dr = color.r() as i32 - entry.r as i32;
+                    let dg = color.g() as i32 - entry.g as i32;
+                    let db = color.b() as i32 - entry.b as i32;
+                    dr * dr + dg * dg + db * db
+                };
+                palette
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, entry)| distance(entry))
+                    .map_or(0, |(index, _)| index as u8)
+            })
+            .collect()
+    }
+
+    /// Counts the occurrences of each value in the R, G, B and A channels, in that order.
+    ///
+    /// Pixels are converted to RGBA as in [`Self::to_rgba_bytes`],
+    /// so images without an alpha channel count every pixel as 255 in the alpha histogram.
+    pub fn channel_histograms(&self) -> [[u32; 256]; 4] {
+        let mut histograms = [[0u32; 256]; 4];
+        for color in self.info.image_type.iter_with_transparency(
+            &self.data,
+            &self.palette,
+            &self.transparency,
+        ) {
+            for (histogram, value) in histograms.iter_mut().zip(color.components().into_array()) {
+                histogram[value as usize] += 1;
+            }
+        }
+        histograms
+    }
+
+    /// Returns the average color of all pixels, rounded to the nearest integer.
+    ///
+    /// Each component, including alpha, is averaged independently.
+    pub fn average_color(&self) -> RGBA8888 {
+        let mut sum = [0u64; 4];
+        let mut count = 0u64;
+        for color in self.info.image_type.iter_with_transparency(
+            &self.data,
+            &self.palette,
+            &self.transparency,
+        ) {
+            sum[0] += color.r() as u64;
+            sum[1] += color.g() as u64;
+            sum[2] += color.b() as u64;
+            sum[3] += color.a() as u64;
+            count += 1;
+        }
+        if count == 0 {
+            return RGBA8888::from_rgba(0, 0, 0, 0);
+        }
+        let [r, g, b, a] = sum.map(|sum| ((sum + count / 2) / count) as u8);
+        RGBA8888::from_rgba(r, g, b, a)
+    }
+}
+
+/// Returns the raw samples of the pixel at `(x, y)`, in the same layout as [`ImageData::raw_data`].
+///
+/// For index color format images, the slice contains the palette index. Use [`ImageData::pixel`] to get the color.
+///
+/// # Panics
+///
+/// Panics if the coordinates are out of range.
+#[cfg(feature = "alloc")]
+impl Index<(u32, u32)> for ImageData {
+    type Output = [u8];
+
+    #[inline]
+    fn index(&self, (x, y): (u32, u32)) -> &Self::Output {
+        assert!(
+            x < self.info.width && y < self.info.height,
+            "pixel ({x}, {y}) is out of range"
+        );
+        let n_channels = self.info.image_type.n_channels();
+        let offset = (y as usize * self.info.width as usize + x as usize) * n_channels;
+        &self.data[offset..offset + n_channels]
+    }
 }
 
+#[cfg(feature = "image")]
+impl ImageData {
+    /// Converts the image into `image::DynamicImage`.
+    ///
+    /// 16-bit images keep their full precision.
+    /// Index color format images are expanded to RGB, or to RGBA if they have an alpha table.
+    pub fn into_dynamic_image(self) -> image::DynamicImage {
+        use image::{DynamicImage, ImageBuffer};
+        let ImageInfo { width, height, .. } = self.info;
+        if self.info.bit_depth == BitDepth::Bpp16 {
+            let data16 = if self.data16.len() == self.data.len() {
+                self.data16
+            } else {
+                self.data.iter().map(|&v| v as u16 * 0x101).collect()
+            };
+            return match self.info.image_type {
+                ImageType::Grayscale => {
+                    DynamicImage::ImageLuma16(ImageBuffer::from_raw(width, height, data16).unwrap())
+                }
+                ImageType::GrayscaleAlpha => DynamicImage::ImageLumaA16(
+                    ImageBuffer::from_raw(width, height, data16).unwrap(),
+                ),
+                ImageType::RGB => {
+                    DynamicImage::ImageRgb16(ImageBuffer::from_raw(width, height, data16).unwrap())
+                }
+                ImageType::RGBA => {
+                    DynamicImage::ImageRgba16(ImageBuffer::from_raw(width, height, data16).unwrap())
+                }
+                // index color format images are at most 8 bits
+                ImageType::Indexed => unreachable!(),
+            };
+        }
+        match self.info.image_type {
+            ImageType::Grayscale => {
+                DynamicImage::ImageLuma8(ImageBuffer::from_raw(width, height, self.data).unwrap())
+            }
+            ImageType::GrayscaleAlpha => {
+                DynamicImage::ImageLumaA8(ImageBuffer::from_raw(width, height, self.data).unwrap())
+            }
+            ImageType::RGB => {
+                DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, self.data).unwrap())
+            }
+            ImageType::RGBA => {
+                DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, self.data).unwrap())
+            }
+            ImageType::Indexed if !self.transparency.is_empty() => {
+                let data = self.to_rgba_bytes().to_vec();
+                DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, data).unwrap())
+            }
+            ImageType::Indexed => {
+                let data = self.to_rgb_bytes().to_vec();
+                DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, data).unwrap())
+            }
+        }
+    }
+}
+
+/// Pixel formats for [`ImageData::to_format`], with 8 bits per sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutputFormat {
+    Rgb8,
+    Rgba8,
+    Bgra8,
+    Gray8,
+    GrayAlpha8,
+}
+
+impl OutputFormat {
+    #[inline]
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            Self::Rgb8 => 3,
+            Self::Rgba8 | Self::Bgra8 => 4,
+            Self::Gray8 => 1,
+            Self::GrayAlpha8 => 2,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
 pub struct RgbaBytes<'a>(Cow<'a, [u8]>);
 
+#[cfg(feature = "alloc")]
 impl Deref for RgbaBytes<'_> {
     type Target = [u8];
 
@@ -193,6 +1385,7 @@ impl Deref for RgbaBytes<'_> {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl DerefMut for RgbaBytes<'_> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
@@ -200,8 +1393,10 @@ impl DerefMut for RgbaBytes<'_> {
     }
 }
 
+#[cfg(feature = "alloc")]
 pub struct RgbBytes<'a>(Cow<'a, [u8]>);
 
+#[cfg(feature = "alloc")]
 impl Deref for RgbBytes<'_> {
     type Target = [u8];
 
@@ -211,6 +1406,7 @@ impl Deref for RgbBytes<'_> {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl DerefMut for RgbBytes<'_> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
@@ -219,11 +1415,13 @@ impl DerefMut for RgbBytes<'_> {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BitDepth {
     Bpp1 = 1,
     Bpp2 = 2,
     Bpp4 = 4,
     Bpp8 = 8,
+    Bpp16 = 16,
 }
 
 impl BitDepth {
@@ -233,6 +1431,7 @@ impl BitDepth {
             2 => Some(Self::Bpp2),
             4 => Some(Self::Bpp4),
             8 => Some(Self::Bpp8),
+            16 => Some(Self::Bpp16),
             _ => None,
         }
     }
@@ -244,6 +1443,31 @@ impl BitDepth {
             Self::Bpp2 => 2,
             Self::Bpp4 => 4,
             Self::Bpp8 => 8,
+            Self::Bpp16 => 16,
         }
     }
+
+    /// Returns the maximum value of a sample, such as 15 for 4-bit samples.
+    #[inline]
+    pub fn max_value(&self) -> u16 {
+        ((1u32 << self.bits_per_pixel()) - 1) as u16
+    }
+
+    /// Maps a sample to the full 8-bit range, so that the maximum value becomes 255.
+    ///
+    /// 16-bit samples are reduced to their most significant byte, in the same way as the decoder.
+    #[inline]
+    pub fn scale_to_8bit(&self, sample: u16) -> u8 {
+        match self {
+            Self::Bpp16 => (sample >> 8) as u8,
+            _ => (sample.min(self.max_value()) * (0xFF / self.max_value())) as u8,
+        }
+    }
+}
+
+impl core::fmt::Display for BitDepth {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}-bit", self.bits_per_pixel())
+    }
 }
@@ -10,30 +10,98 @@
 
 #![cfg_attr(not(test), no_std)]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "alloc")]
 use alloc::borrow::Cow;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
-use color::RGB888;
+#[cfg(feature = "alloc")]
+use color::{RGB888, RGBA8888};
+#[cfg(feature = "alloc")]
 use compress::deflate::Deflate;
 use core::ops::{Deref, DerefMut};
 use core::slice;
 
 pub mod color;
+pub mod filter;
+#[cfg(feature = "alloc")]
+use filter::{average, paeth};
 
 mod image_data;
 pub use image_data::*;
 
+mod ancillary;
+pub use ancillary::*;
+
+mod crc;
+pub use crc::*;
+
+mod observer;
+pub use observer::*;
+
+#[cfg(feature = "alloc")]
+mod encoder;
+#[cfg(feature = "alloc")]
+pub use encoder::*;
+
 pub const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\x0D\x0A\x1A\x0A";
 
+/// Returns `true` if the bytes start with the PNG signature.
+///
+/// Only the signature is checked, so this is suitable for sniffing the content type.
+#[inline]
+pub fn is_png(bytes: &[u8]) -> bool {
+    bytes.starts_with(PNG_SIGNATURE)
+}
+
+/// Decodes a PNG image and returns the image information and the image data in RGBA format.
+#[cfg(feature = "alloc")]
+pub fn decode_rgba(input: &[u8]) -> Result<(ImageInfo, Vec<u8>), DecodeError> {
+    let decoded = PngDecoder::new(input)?.decode()?;
+    let data = decoded.to_rgba_bytes().to_vec();
+    Ok((decoded.info, data))
+}
+
+/// Decodes a PNG image and returns the image information and the image data in RGB format.
+#[cfg(feature = "alloc")]
+pub fn decode_rgb(input: &[u8]) -> Result<(ImageInfo, Vec<u8>), DecodeError> {
+    let decoder = PngDecoder::new(input)?;
+    let data = decoder.decode_to_rgb()?;
+    Ok((decoder.info, data))
+}
+
 pub struct PngDecoder<'a> {
     slice: &'a [u8],
     info: ImageInfo,
+    ihdr_crc_valid: bool,
+    lenient: bool,
+    strict_idat: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DecodeError {
     InvalidData,
     UnsupportedFormat,
+    /// The PLTE chunk has too many entries for the bit depth, or too few for the indices used
+    InvalidPalette,
+    /// The CRC of a chunk does not match its contents
+    CrcMismatch,
+    /// The compression method in the IHDR chunk is not supported
+    UnsupportedCompressionMethod(u8),
+    /// The filter method in the IHDR chunk is not supported
+    UnsupportedFilterMethod(u8),
+    /// The interlace method in the IHDR chunk is not supported
+    UnsupportedInterlaceMethod(u8),
+    /// The bit depth is not allowed for the color type, such as 16-bit index color
+    InvalidBitDepth {
+        color_type: u8,
+        bit_depth: u8,
+    },
+    /// The zlib header is broken, or requires a compression method or a preset dictionary other than deflate without a dictionary
+    InvalidZlibHeader,
+    /// The memory for the decoded image could not be allocated
+    OutOfMemory,
 }
 
 impl<'a> PngDecoder<'a> {
@@ -51,7 +119,10 @@ impl<'a> PngDecoder<'a> {
         let Some((ihdr, next)) = next.split_at_checked(25) else {
             return Err(DecodeError::InvalidData);
         };
-        let mut ihdr = ChunksInner { iter: ihdr.iter() };
+        let mut ihdr = ChunksInner {
+            iter: ihdr.iter(),
+            implicit_iend: false,
+        };
         let ihdr = ihdr.next_chunk()?;
         if ihdr.chunk_type() != FourCC::IHDR {
             return Err(DecodeError::InvalidData);
@@ -59,52 +130,143 @@ impl<'a> PngDecoder<'a> {
         if ihdr.len() != 13 {
             return Err(DecodeError::InvalidData);
         }
-        let width = Be32(ihdr.data()[0..4].try_into().unwrap()).as_u32();
-        let height = Be32(ihdr.data()[4..8].try_into().unwrap()).as_u32();
-        if width == 0 || height == 0 {
-            return Err(DecodeError::InvalidData);
-        }
-        if cfg!(target_pointer_width = "32") && (width.saturating_mul(height) > 0x1000_0000) {
-            // maybe overflow
-            return Err(DecodeError::UnsupportedFormat);
+        let info = ImageInfo::from_ihdr_data(ihdr.data().try_into().unwrap())?;
+
+        Ok(PngDecoder {
+            slice: next,
+            info,
+            ihdr_crc_valid: ihdr.crc_matches_computed(),
+            lenient: false,
+            strict_idat: false,
+        })
+    }
+
+    /// Generates a PNG decoder from an already parsed image information and the remaining chunks.
+    ///
+    /// `chunks` must be positioned after the IHDR chunk and before the PLTE and IDAT chunks,
+    /// for example after skipping ancillary chunks that were handled by the caller.
+    ///
+    /// Returns an error if the image information is invalid, as checked by [`ImageInfo::validate`].
+    #[inline]
+    pub fn from_parts(info: ImageInfo, chunks: Chunks<'a>) -> Result<PngDecoder<'a>, DecodeError> {
+        info.validate()?;
+        Ok(PngDecoder {
+            slice: chunks.inner.iter.as_slice(),
+            info,
+            ihdr_crc_valid: true,
+            lenient: chunks.inner.implicit_iend,
+            strict_idat: false,
+        })
+    }
+
+    /// Sets whether to recover from common defects instead of returning an error.
+    ///
+    /// In lenient mode:
+    ///
+    /// * The end of the input at a chunk boundary is treated as an implicit IEND chunk.
+    /// * A trailing partial entry in the PLTE chunk is ignored.
+    ///
+    /// The default is strict.
+    #[inline]
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    #[inline]
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// Sets whether the IDAT chunks must be consecutive, as required by the specification.
+    ///
+    /// When enabled, decoding and verification return an error if any other chunk appears
+    /// between the first and the last IDAT chunk.
+    /// The default accepts ancillary chunks between IDAT chunks.
+    #[inline]
+    pub fn strict_idat(mut self, strict_idat: bool) -> Self {
+        self.strict_idat = strict_idat;
+        self
+    }
+
+    #[inline]
+    pub fn is_strict_idat(&self) -> bool {
+        self.strict_idat
+    }
+
+    /// Checks that no other chunk appears between the IDAT chunks, if required.
+    fn check_idat_sequence(&self) -> Result<(), DecodeError> {
+        if !self.strict_idat {
+            return Ok(());
         }
-        let Some(bit_depth) = BitDepth::new(ihdr.data()[8]) else {
-            return Err(DecodeError::UnsupportedFormat);
-        };
-        let color_type = ihdr.data()[9];
-        let image_type = match (color_type, bit_depth) {
-            (0, BitDepth::Bpp8) => ImageType::Grayscale,
-            (2, BitDepth::Bpp8) => ImageType::RGB,
-            (3, BitDepth::Bpp1)
-            | (3, BitDepth::Bpp2)
-            | (3, BitDepth::Bpp4)
-            | (3, BitDepth::Bpp8) => ImageType::Indexed,
-            (4, BitDepth::Bpp8) => ImageType::GrayscaleAlpha,
-            (6, BitDepth::Bpp8) => ImageType::RGBA,
-            _ => return Err(DecodeError::UnsupportedFormat),
-        };
-        let compression_method = ihdr.data()[10];
-        let filter_method = ihdr.data()[11];
-        let interlace_method = ihdr.data()[12];
-        // currently not supported
-        if compression_method != 0 || filter_method != 0 || interlace_method != 0 {
-            return Err(DecodeError::UnsupportedFormat);
+        let mut chunks = self.chunks_unchecked();
+        let mut has_idat = false;
+        let mut idat_ended = false;
+        loop {
+            let chunk = chunks.next_chunk()?;
+            match chunk.chunk_type() {
+                FourCC::IEND => return Ok(()),
+                FourCC::IDAT => {
+                    if idat_ended {
+                        return Err(DecodeError::InvalidData);
+                    }
+                    has_idat = true;
+                }
+                _ => idat_ended = has_idat,
+            }
         }
+    }
 
-        let info = ImageInfo {
-            width,
-            height,
-            bit_depth,
-            image_type,
-        };
+    /// Generates a PNG decoder from the first PNG signature found in the specified slice.
+    ///
+    /// This is a recovery mode for files with junk bytes before the signature, such as a UTF-8 BOM.
+    pub fn scan(input: &'a [u8]) -> Result<PngDecoder<'a>, DecodeError> {
+        let offset = Self::find_signature(input).ok_or(DecodeError::InvalidData)?;
+        Self::new(&input[offset..])
+    }
 
-        Ok(PngDecoder { slice: next, info })
+    /// Returns the offset of the first PNG signature in the specified slice.
+    pub fn find_signature(input: &[u8]) -> Option<usize> {
+        input
+            .windows(PNG_SIGNATURE.len())
+            .position(|window| window == PNG_SIGNATURE)
+    }
+
+    /// Decodes each of the PNG files concatenated in the specified slice.
+    ///
+    /// After the IEND chunk of each file, decoding resumes at the next PNG signature.
+    /// The iterator stops after the first error.
+    #[cfg(feature = "alloc")]
+    pub fn decode_all(
+        input: &'a [u8],
+    ) -> impl Iterator<Item = Result<ImageData, DecodeError>> + 'a {
+        let mut remaining = input;
+        core::iter::from_fn(move || {
+            let offset = Self::find_signature(remaining)?;
+            let result = Self::new(&remaining[offset..]).and_then(|decoder| {
+                let image = decoder.decode()?;
+                let mut chunks = decoder.chunks_unchecked();
+                while !chunks.next_chunk()?.is_iend() {}
+                Ok((image, chunks.iter.as_slice()))
+            });
+            match result {
+                Ok((image, next)) => {
+                    remaining = next;
+                    Some(Ok(image))
+                }
+                Err(err) => {
+                    remaining = &[];
+                    Some(Err(err))
+                }
+            }
+        })
     }
 
     #[inline]
     pub fn chunks_unchecked(&self) -> ChunksInner<'a> {
         ChunksInner {
             iter: self.slice.iter(),
+            implicit_iend: self.lenient,
         }
     }
 
@@ -129,20 +291,89 @@ impl<'a> PngDecoder<'a> {
         &self.info
     }
 
-    /// Decodes PNG images and returns image data.
-    pub fn decode(&self) -> Result<ImageData, DecodeError> {
-        let mut chunks = self.chunks()?;
+    /// Checks the structure of the PNG file without decoding the image data.
+    ///
+    /// Walks all chunks and validates their framing and ordering, and optionally their CRCs.
+    /// Returns the first problem found.
+    pub fn verify(&self, check_crc: bool) -> Result<(), DecodeError> {
+        if check_crc && !self.ihdr_crc_valid {
+            return Err(DecodeError::CrcMismatch);
+        }
+        let mut chunks = self.chunks_unchecked();
+        let mut has_palette = false;
+        let mut has_idat = false;
+        loop {
+            let chunk = chunks.next_chunk()?;
+            if check_crc && !chunk.crc_matches_computed() {
+                return Err(DecodeError::CrcMismatch);
+            }
+            match chunk.chunk_type() {
+                FourCC::IEND => break,
+                FourCC::IHDR => return Err(DecodeError::InvalidData),
+                FourCC::PLTE => {
+                    if has_palette
+                        || has_idat
+                        || chunk.is_empty()
+                        || (chunk.len() % 3 != 0 && !self.lenient)
+                        || self.info.image_type.is_gray_scale()
+                    {
+                        return Err(DecodeError::InvalidData);
+                    }
+                    has_palette = true;
+                }
+                FourCC::IDAT => has_idat = true,
+                four_cc => {
+                    if four_cc.is_critical() {
+                        return Err(DecodeError::UnsupportedFormat);
+                    }
+                }
+            }
+        }
+        if !has_idat || (self.info.image_type == ImageType::Indexed && !has_palette) {
+            return Err(DecodeError::InvalidData);
+        }
+        self.check_idat_sequence()
+    }
+
+    /// Returns the palette of index color format images without decoding the image data.
+    ///
+    /// Returns `None` for other image types.
+    #[cfg(feature = "alloc")]
+    pub fn palette(&self) -> Result<Option<Vec<RGB888>>, DecodeError> {
+        if self.info.image_type != ImageType::Indexed {
+            return Ok(None);
+        }
+        let (_, header) = self.read_chunks_before_idat(self.chunks()?.inner)?;
+        header.palette.ok_or(DecodeError::InvalidData).map(Some)
+    }
+
+    /// Reads chunks before IDAT and returns the chunks positioned at the first IDAT.
+    #[cfg(feature = "alloc")]
+    fn read_chunks_before_idat(
+        &self,
+        mut chunks: ChunksInner<'a>,
+    ) -> Result<(ChunksInner<'a>, HeaderChunks), DecodeError> {
         let mut palette = Option::<Vec<RGB888>>::None;
+        let mut transparency = Option::<Vec<u8>>::None;
+        let mut background = Option::<RGBA8888>::None;
 
-        // Read chunks before IDAT
         loop {
-            let chunk = chunks.peek_chunk()?;
+            let chunk = match chunks.peek_chunk() {
+                Ok(chunk) => chunk,
+                // a truncated IDAT chunk is left to the image stream
+                Err(_) if chunks.partial_idat().is_some() => break,
+                Err(err) => return Err(err),
+            };
             match chunk.chunk_type() {
                 FourCC::IDAT => break,
                 FourCC::PLTE => {
-                    if chunk.len() % 3 != 0 || palette.is_some() {
+                    if (chunk.len() % 3 != 0 && !self.lenient) || palette.is_some() {
                         return Err(DecodeError::InvalidData);
                     }
+                    // also applies to the suggested palette of truecolor images
+                    if !(1..=256).contains(&(chunk.len() / 3)) {
+                        return Err(DecodeError::InvalidPalette);
+                    }
                     palette = Some(
                         chunk
                             .data()
@@ -151,6 +382,26 @@ impl<'a> PngDecoder<'a> {
                             .collect(),
                     );
                 }
+                FourCC::tRNS => {
+                    // only the alpha table of index color format images is used
+                    if self.info.image_type == ImageType::Indexed {
+                        if palette.is_none() || transparency.is_some() {
+                            return Err(DecodeError::InvalidData);
+                        }
+                        transparency = Some(chunk.data().to_vec());
+                    }
+                }
+                FourCC::bKGD => {
+                    if background.is_some() {
+                        return Err(DecodeError::InvalidData);
+                    }
+                    // a malformed background color is only a missing hint
+                    background = parse_background(
+                        chunk.data(),
+                        &self.info,
+                        palette.as_deref().unwrap_or_default(),
+                    );
+                }
                 four_cc => {
                     if four_cc.is_critical() {
                         return Err(DecodeError::UnsupportedFormat);
@@ -160,32 +411,547 @@ impl<'a> PngDecoder<'a> {
             chunks.next_chunk()?;
         }
 
+        Ok((
+            chunks,
+            HeaderChunks {
+                palette,
+                transparency,
+                background,
+            },
+        ))
+    }
+
+    /// Returns the image offset from the `oFFs` chunk, if any.
+    pub fn offset(&self) -> Result<Option<ImageOffset>, DecodeError> {
+        self.find_chunk(FourCC::oFFs)?
+            .map(|chunk| ImageOffset::parse(chunk.data()))
+            .transpose()
+    }
+
+    /// Returns the decompressed ICC profile from the `iCCP` chunk, if any.
+    #[cfg(feature = "alloc")]
+    pub fn icc_profile(&self) -> Result<Option<Vec<u8>>, DecodeError> {
+        self.find_chunk(FourCC::iCCP)?
+            .map(|chunk| parse_icc_profile(chunk.data()))
+            .transpose()
+    }
+
+    /// Returns the suggested palettes from the `sPLT` chunks, in order of appearance.
+    ///
+    /// Returns an error if two suggested palettes have the same name.
+    #[cfg(feature = "alloc")]
+    pub fn suggested_palettes(&self) -> Result<Vec<SuggestedPalette>, DecodeError> {
+        let mut result = Vec::<SuggestedPalette>::new();
+        for chunk in self.chunks()?.inner {
+            if chunk.chunk_type() != FourCC::sPLT {
+                continue;
+            }
+            let palette = SuggestedPalette::parse(chunk.data())?;
+            if result.iter().any(|v| v.name == palette.name) {
+                return Err(DecodeError::InvalidData);
+            }
+            result.push(palette);
+        }
+        Ok(result)
+    }
+
+    /// Returns the ratio of the size of the filtered image stream to the size of the compressed IDAT data.
+    ///
+    /// The image data is not decompressed, the stream size is computed from the image information.
+    #[cfg(feature = "alloc")]
+    pub fn compression_ratio(&self) -> Result<f32, DecodeError> {
+        let compressed = self.chunks()?.get_idat_chunks(false)?.len();
+        let inflated = self.info.image_stream_len()?;
+        Ok(inflated as f32 / compressed as f32)
+    }
+
+    /// Returns the GIF extensions from the `gIFg` and `gIFx` chunks, in order of appearance.
+    ///
+    /// These chunks are left by converters from GIF files.
+    #[cfg(feature = "alloc")]
+    pub fn gif_extensions(&self) -> Result<GifExtensions<'a>, DecodeError> {
+        let mut result = GifExtensions::default();
+        for chunk in self.chunks()?.inner {
+            match chunk.chunk_type() {
+                FourCC::gIFg => result
+                    .graphic_controls
+                    .push(GifGraphicControl::parse(chunk.data())?),
+                FourCC::gIFx => result
+                    .applications
+                    .push(GifApplicationExtension::parse(chunk.data())?),
+                _ => {}
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns the types of ancillary chunks that this decoder does not interpret, in order of first appearance.
+    ///
+    /// Each chunk type is listed only once.
+    #[cfg(feature = "alloc")]
+    pub fn unknown_chunks(&self) -> Result<Vec<FourCC>, DecodeError> {
+        let mut result = Vec::new();
+        for chunk in self.chunks()?.inner {
+            let chunk_type = chunk.chunk_type();
+            match chunk_type {
+                FourCC::tRNS
+                | FourCC::oFFs
+                | FourCC::iCCP
+                | FourCC::sPLT
+                | FourCC::gIFg
+                | FourCC::gIFx
+                | FourCC::bKGD => {}
+                _ => {
+                    if chunk_type.is_ancillary() && !result.contains(&chunk_type) {
+                        result.push(chunk_type);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns the first chunk of the specified type.
+    #[inline]
+    fn find_chunk(&self, chunk_type: FourCC) -> Result<Option<PngChunk<'a>>, DecodeError> {
+        self.chunks()?.skip_to(chunk_type)
+    }
+
+    /// Decodes PNG images and returns image data.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn decode(&self) -> Result<ImageData, DecodeError> {
+        self.decode_with_observer(&mut ())
+    }
+
+    /// Decodes PNG images and reports the progress of each phase to the observer.
+    #[cfg(feature = "alloc")]
+    pub fn decode_with_observer<O>(&self, observer: &mut O) -> Result<ImageData, DecodeError>
+    where
+        O: DecodeObserver + ?Sized,
+    {
+        self.decode_image(None, observer)
+    }
+
+    /// Decodes index color format images with the specified palette instead of the PLTE chunk.
+    ///
+    /// The indices are kept as is, and the palette is used for the output conversion.
+    /// The palette may have up to 256 entries regardless of the bit depth.
+    /// Returns an error if the palette does not cover all indices used, or for other image types.
+    #[cfg(feature = "alloc")]
+    pub fn decode_with_palette(&self, palette: &[RGB888]) -> Result<ImageData, DecodeError> {
+        if self.info.image_type != ImageType::Indexed {
+            return Err(DecodeError::UnsupportedFormat);
+        }
+        self.decode_image(Some(palette), &mut ())
+    }
+
+    #[cfg(feature = "alloc")]
+    fn decode_image<O>(
+        &self,
+        palette: Option<&[RGB888]>,
+        observer: &mut O,
+    ) -> Result<ImageData, DecodeError>
+    where
+        O: DecodeObserver + ?Sized,
+    {
+        // the output is allocated only after the image stream is known to be complete
+        let (header, inflated) = self.inflate_image(palette, observer)?;
+        self.check_image_stream_len(&inflated)?;
+        let mut data = try_alloc(self.info.checked_decoded_len()?)?;
+        let mut data16 = Vec::new();
+        if self.info.bit_depth == BitDepth::Bpp16 {
+            data16
+                .try_reserve_exact(data.len())
+                .map_err(|_| DecodeError::OutOfMemory)?;
+        }
+        self.reconstruct_image(
+            &header,
+            &inflated,
+            &mut data,
+            Some(&mut data16),
+            palette.is_some(),
+            observer,
+        )?;
+
+        // return the image data
+        Ok(ImageData {
+            info: self.info,
+            palette: header.palette.unwrap_or_default(),
+            transparency: header.transparency.unwrap_or_default(),
+            background: header.background,
+            data,
+            data16,
+        })
+    }
+
+    /// Decodes PNG images and returns the statistics of the image stream, such as the filter types used.
+    #[cfg(feature = "alloc")]
+    pub fn decode_with_stats(&self) -> Result<(ImageData, DecodeStats), DecodeError> {
+        let mut stats = DecodeStats::default();
+        let decoded = self.decode_with_observer(&mut stats)?;
+        Ok((decoded, stats))
+    }
+
+    /// Decodes PNG images and returns the type and data length of each chunk, in order of appearance.
+    ///
+    /// The list is collected while decoding, so the chunks are not walked again.
+    #[cfg(feature = "alloc")]
+    pub fn decode_with_chunk_list(&self) -> Result<(ImageData, Vec<(FourCC, usize)>), DecodeError> {
+        let mut chunks = Vec::new();
+        let decoded = self.decode_with_observer(&mut chunks)?;
+        Ok((decoded, chunks))
+    }
+
+    /// Decodes PNG images and returns the image data in RGB format.
+    ///
+    /// For index color format images, the palette is applied while the scanlines are reconstructed,
+    /// so the indices are never stored for the whole image.
+    /// Other formats are decoded and then converted in the same way as [`ImageData::to_rgb_bytes`].
+    #[cfg(feature = "alloc")]
+    pub fn decode_to_rgb(&self) -> Result<Vec<u8>, DecodeError> {
+        if self.info.image_type != ImageType::Indexed {
+            return Ok(self.decode()?.to_rgb_bytes().to_vec());
+        }
+        let (mut chunks, header) = self.read_chunks_before_idat(self.chunks()?.inner)?;
+
+        // Get IDAT chunks
+        self.check_idat_sequence()?;
+        let data = chunks.get_idat_chunks(true)?;
+        check_zlib_header(&data)?;
+
+        // Decompress the IDAT data
+        let inflated = self.inflate_image_stream(&data)?;
+        self.check_image_stream_len(&inflated)?;
+
+        // the indices are checked against the palette while they are applied
+        self.check_palette(&header, &[], false)?;
+        let palette = header.palette.as_deref().unwrap_or_default();
+
+        // process filters and apply the palette
+        let mut indices = try_alloc(self.info.width as usize)?;
+        let mut output = Vec::new();
+        let output_len = self.info.checked_decoded_len()?.checked_mul(3);
+        output
+            .try_reserve_exact(output_len.ok_or(DecodeError::OutOfMemory)?)
+            .map_err(|_| DecodeError::OutOfMemory)?;
+        let mut valid_indices = true;
+        self.reconstruct(&inflated, |_, line| {
+            self.unpack_line(line, &mut indices);
+            for &index in indices.iter() {
+                match palette.get(index as usize) {
+                    Some(color) => output.extend_from_slice(&[color.r, color.g, color.b]),
+                    None => valid_indices = false,
+                }
+            }
+        })?;
+        if !valid_indices {
+            return Err(DecodeError::InvalidPalette);
+        }
+
+        Ok(output)
+    }
+
+    /// Decodes PNG images after checking the CRCs of the chunks of the specified types.
+    ///
+    /// Chunks of other types are not checked, so trusted pipelines can validate only what they care about,
+    /// for example `&[FourCC::IHDR, FourCC::IDAT]`.
+    #[cfg(feature = "alloc")]
+    pub fn decode_checked(&self, crc_chunks: &[FourCC]) -> Result<ImageData, DecodeError> {
+        if crc_chunks.contains(&FourCC::IHDR) && !self.ihdr_crc_valid {
+            return Err(DecodeError::CrcMismatch);
+        }
+        let mut chunks = self.chunks()?;
+        loop {
+            let chunk = chunks.next_chunk()?;
+            if crc_chunks.contains(&chunk.chunk_type()) && !chunk.crc_matches_computed() {
+                return Err(DecodeError::CrcMismatch);
+            }
+            if chunk.is_iend() {
+                break;
+            }
+        }
+        self.decode()
+    }
+
+    /// Decodes PNG images into the specified buffer.
+    ///
+    /// The layout of the buffer is the same as [`ImageData::raw_data`],
+    /// so its size must be exactly `width * height * n_channels` bytes.
+    #[cfg(feature = "alloc")]
+    pub fn decode_into(&self, buf: &mut [u8]) -> Result<(), DecodeError> {
+        if buf.len() != self.info.checked_decoded_len()? {
+            return Err(DecodeError::InvalidData);
+        }
+        self.decode_inner(buf, None, None, &mut ()).map(|_| ())
+    }
+
+    /// Decodes PNG images into the specified fixed-size array without allocating the output buffer.
+    ///
+    /// Returns an error if `N` does not match the size of the decoded image.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn decode_into_array<const N: usize>(&self, buf: &mut [u8; N]) -> Result<(), DecodeError> {
+        self.decode_into(buf.as_mut_slice())
+    }
+
+    #[cfg(feature = "alloc")]
+    fn decode_inner<O>(
+        &self,
+        buf: &mut [u8],
+        data16: Option<&mut Vec<u16>>,
+        palette: Option<&[RGB888]>,
+        observer: &mut O,
+    ) -> Result<HeaderChunks, DecodeError>
+    where
+        O: DecodeObserver + ?Sized,
+    {
+        let (header, inflated) = self.inflate_image(palette, observer)?;
+        self.reconstruct_image(&header, &inflated, buf, data16, palette.is_some(), observer)?;
+        Ok(header)
+    }
+
+    /// Checks the chunks, reads the chunks before IDAT and decompresses the image stream.
+    #[cfg(feature = "alloc")]
+    fn inflate_image<O>(
+        &self,
+        palette: Option<&[RGB888]>,
+        observer: &mut O,
+    ) -> Result<(HeaderChunks, Vec<u8>), DecodeError>
+    where
+        O: DecodeObserver + ?Sized,
+    {
+        // check that the chunks are complete, reporting them at the same time
+        observer.chunk(FourCC::IHDR, 13);
+        let mut test = self.chunks_unchecked();
+        loop {
+            let chunk = test.next_chunk()?;
+            observer.chunk(chunk.chunk_type(), chunk.len());
+            if chunk.is_iend() {
+                break;
+            }
+        }
+
+        let (mut chunks, mut header) = self.read_chunks_before_idat(self.chunks_unchecked())?;
+        if let Some(palette) = palette {
+            header.palette = Some(palette.to_vec());
+        }
+
         // Get IDAT chunks
+        self.check_idat_sequence()?;
         let data = chunks.get_idat_chunks(true)?;
+        check_zlib_header(&data)?;
 
         // Decompress the IDAT data
-        let inflated = Deflate::inflate(
-            &data,
-            (1 + self.info.width as usize * self.info.image_type.n_channels() as usize)
-                * self.info.height as usize,
-        )
-        .map_err(|_| DecodeError::InvalidData)?;
+        observer.phase_started(DecodePhase::Inflate);
+        let inflated = self.inflate_image_stream(&data)?;
+        observer.phase_finished(DecodePhase::Inflate, data.len(), inflated.len());
+
+        Ok((header, inflated))
+    }
 
+    /// Reconstructs the decompressed image stream into the output buffer.
+    #[cfg(feature = "alloc")]
+    fn reconstruct_image<O>(
+        &self,
+        header: &HeaderChunks,
+        inflated: &[u8],
+        buf: &mut [u8],
+        mut data16: Option<&mut Vec<u16>>,
+        is_substitute: bool,
+        observer: &mut O,
+    ) -> Result<(), DecodeError>
+    where
+        O: DecodeObserver + ?Sized,
+    {
         // process filters
-        let stride = if self.info.bit_depth > BitDepth::Bpp8 {
-            self.info.width as usize * self.info.image_type.n_channels() as usize
+        observer.phase_started(DecodePhase::Reconstruct);
+        for (y, line) in inflated
+            .chunks_exact(1 + self.info.stride())
+            .take(self.info.height as usize)
+            .enumerate()
+        {
+            if let Some(filter_type) = FilterType::new(line[0]) {
+                observer.scanline_filter(y, filter_type);
+            }
+        }
+        let row_len = self.info.width as usize * self.info.image_type.n_channels();
+        self.reconstruct(inflated, |y, line| {
+            self.unpack_line(line, &mut buf[y * row_len..(y + 1) * row_len]);
+            if let Some(data16) = data16.as_mut() {
+                self.unpack_line16(line, data16);
+            }
+        })?;
+        observer.phase_finished(DecodePhase::Reconstruct, inflated.len(), buf.len());
+
+        self.check_palette(header, buf, is_substitute)
+    }
+
+    /// Decodes as many complete scanlines as the available data allows.
+    ///
+    /// Returns the image data and the number of valid rows.
+    /// Rows after the last valid row are filled with zeros.
+    /// The IEND chunk is not required, and a short or broken image stream stops decoding
+    /// instead of returning an error.
+    /// A file cut in the last IDAT chunk is accepted as long as the zlib stream itself is complete,
+    /// a zlib stream cut in the middle cannot be decompressed and returns an error.
+    #[cfg(feature = "alloc")]
+    pub fn decode_partial(&self) -> Result<(ImageData, usize), DecodeError> {
+        let (mut chunks, header) = self.read_chunks_before_idat(self.chunks_unchecked())?;
+
+        // Get available IDAT chunks
+        let mut data = Vec::new();
+        loop {
+            match chunks.next_chunk() {
+                Ok(chunk) => {
+                    if chunk.is_iend() {
+                        break;
+                    }
+                    if chunk.chunk_type() == FourCC::IDAT {
+                        data.extend_from_slice(chunk.data());
+                    }
+                }
+                Err(_) => {
+                    // the file may end in the middle of an IDAT chunk
+                    if let Some(partial) = chunks.partial_idat() {
+                        data.extend_from_slice(partial);
+                    }
+                    break;
+                }
+            }
+        }
+
+        // Decompress the IDAT data
+        check_zlib_header(&data)?;
+        let inflated = self.inflate_image_stream(&data)?;
+
+        // process filters until the data runs out
+        let mut buf = try_alloc(self.info.checked_decoded_len()?)?;
+        let row_len = self.info.width as usize * self.info.image_type.n_channels();
+        let mut data16 = Vec::new();
+        let mut valid_rows = 0;
+        let _ = self.reconstruct(&inflated, |y, line| {
+            self.unpack_line(line, &mut buf[y * row_len..(y + 1) * row_len]);
+            self.unpack_line16(line, &mut data16);
+            valid_rows = y + 1;
+        });
+        if self.info.bit_depth == BitDepth::Bpp16 {
+            data16
+                .try_reserve_exact(buf.len() - data16.len())
+                .map_err(|_| DecodeError::OutOfMemory)?;
+            data16.resize(buf.len(), 0);
+        }
+
+        self.check_palette(&header, &buf[..valid_rows * row_len], false)?;
+
+        Ok((
+            ImageData {
+                info: self.info,
+                palette: header.palette.unwrap_or_default(),
+                transparency: header.transparency.unwrap_or_default(),
+                background: header.background,
+                data: buf,
+                data16,
+            },
+            valid_rows,
+        ))
+    }
+
+    /// Decompresses the image stream.
+    ///
+    /// The expected size is only a hint for the decompressor, and is bounded by the size of the
+    /// compressed data, so a header declaring a huge image does not reserve memory by itself.
+    #[cfg(feature = "alloc")]
+    fn inflate_image_stream(&self, data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        // deflate cannot expand data by more than 1032 times
+        let size_hint = self
+            .info
+            .image_stream_len()?
+            .min(data.len().saturating_mul(1032));
+        Deflate::inflate(data, size_hint).map_err(|_| DecodeError::InvalidData)
+    }
+
+    /// Checks that the decompressed image stream covers every scanline,
+    /// before the output buffer is allocated.
+    #[cfg(feature = "alloc")]
+    fn check_image_stream_len(&self, inflated: &[u8]) -> Result<(), DecodeError> {
+        if inflated.len() < self.info.image_stream_len()? {
+            return Err(DecodeError::InvalidData);
+        }
+        Ok(())
+    }
+
+    /// Checks that the palette can be used with the decoded indices.
+    ///
+    /// A substitute for the PLTE chunk may have more entries than the bit depth can address.
+    #[cfg(feature = "alloc")]
+    fn check_palette(
+        &self,
+        header: &HeaderChunks,
+        data: &[u8],
+        is_substitute: bool,
+    ) -> Result<(), DecodeError> {
+        if self.info.image_type != ImageType::Indexed {
+            return Ok(());
+        }
+        let Some(palette) = header.palette.as_ref() else {
+            return Err(DecodeError::InvalidData);
+        };
+        // the number of entries must not exceed the range that the bit depth can address
+        let max_entries = if is_substitute {
+            256
+        } else {
+            1usize << self.info.bit_depth.bits_per_pixel()
+        };
+        if palette.is_empty() || palette.len() > max_entries {
+            return Err(DecodeError::InvalidPalette);
+        }
+        let max_index = data.iter().copied().max().unwrap_or_default() as usize;
+        if max_index >= palette.len() {
+            return Err(DecodeError::InvalidPalette);
+        }
+        // the alpha table must not be longer than the palette
+        if header
+            .transparency
+            .as_ref()
+            .is_some_and(|transparency| transparency.len() > palette.len())
+        {
+            return Err(DecodeError::InvalidData);
+        }
+        Ok(())
+    }
+
+    /// Reconstructs the filtered scanlines and passes each line to the kernel.
+    #[cfg(feature = "alloc")]
+    fn reconstruct<F>(&self, inflated: &[u8], mut kernel: F) -> Result<(), DecodeError>
+    where
+        F: FnMut(usize, &[u8]),
+    {
+        let stride = self.info.stride();
+        // distance to the corresponding byte of the left pixel
+        let bpp = if self.info.bit_depth > BitDepth::Bpp8 {
+            self.info.image_type.n_channels() * 2
         } else {
-            (self.info.width as usize
-                * self.info.image_type.n_channels() as usize
-                * self.info.bit_depth as usize
-                + 7)
-                / 8
+            self.info.image_type.n_channels()
         };
-        let mut source = inflated.as_slice();
-        let mut reconstructed = Vec::with_capacity(stride * self.info.height as usize);
-        let mut prev_line = Vec::with_capacity(stride);
+        let height = self.info.height as usize;
+        // fast path: if no scanline is filtered, lines are passed straight from the inflated data
+        if let Some(lines) = inflated.get(..self.info.image_stream_len()?)
+            && lines
+                .chunks_exact(1 + stride)
+                .all(|line| line[0] == FilterType::None.as_u8())
+        {
+            for (y, line) in lines.chunks_exact(1 + stride).enumerate() {
+                kernel(y, &line[1..]);
+            }
+            return Ok(());
+        }
+
+        let mut source = inflated;
+        // the previous line of the first scanline is treated as all zeros
+        let mut prev_line = alloc::vec![0; stride];
         let mut line = Vec::with_capacity(stride);
-        for _y in 0..self.info.height as usize {
+        for y in 0..height {
             let Some((filter_type, next)) = source.split_at_checked(1) else {
                 return Err(DecodeError::InvalidData);
             };
@@ -198,7 +964,7 @@ impl<'a> PngDecoder<'a> {
                 FilterType::None => {
                     line.extend_from_slice(line_src);
                 }
-                FilterType::Sub => match self.info.image_type.n_channels() {
+                FilterType::Sub => match bpp {
                     1 => {
                         let mut prev = 0;
                         for &byte in line_src.iter() {
@@ -258,18 +1024,19 @@ impl<'a> PngDecoder<'a> {
                             prev_a = a;
                         }
                     }
-                    _ => unreachable!(),
+                    _ => {
+                        for (i, &x) in line_src.iter().enumerate() {
+                            let left = if i >= bpp { line[i - bpp] } else { 0 };
+                            line.push(x.wrapping_add(left));
+                        }
+                    }
                 },
                 FilterType::Up => {
-                    if prev_line.is_empty() {
-                        line.extend_from_slice(line_src);
-                    } else {
-                        for (&x, &above) in line_src.iter().zip(prev_line.iter()) {
-                            line.push(x.wrapping_add(above));
-                        }
+                    for (&x, &above) in line_src.iter().zip(prev_line.iter()) {
+                        line.push(x.wrapping_add(above));
                     }
                 }
-                FilterType::Average => match self.info.image_type.n_channels() {
+                FilterType::Average => match bpp {
                     1 => {
                         let mut prev = 0;
                         for (x, &above) in line_src.iter().zip(prev_line.iter()) {
@@ -332,9 +1099,14 @@ impl<'a> PngDecoder<'a> {
                             prev_a = a;
                         }
                     }
-                    _ => unreachable!(),
+                    _ => {
+                        for (i, (&x, &above)) in line_src.iter().zip(prev_line.iter()).enumerate() {
+                            let left = if i >= bpp { line[i - bpp] } else { 0 };
+                            line.push(x.wrapping_add(average(above, left)));
+                        }
+                    }
                 },
-                FilterType::Paeth => match self.info.image_type.n_channels() {
+                FilterType::Paeth => match bpp {
                     1 => {
                         let mut left = 0;
                         let mut upper_left = 0;
@@ -417,105 +1189,173 @@ impl<'a> PngDecoder<'a> {
                             upper_left_a = a_a;
                         }
                     }
-                    _ => unreachable!(),
+                    _ => {
+                        for (i, (&x, &above)) in line_src.iter().zip(prev_line.iter()).enumerate() {
+                            let (left, upper_left) = if i >= bpp {
+                                (line[i - bpp], prev_line[i - bpp])
+                            } else {
+                                (0, 0)
+                            };
+                            line.push(x.wrapping_add(paeth(left, above, upper_left)));
+                        }
+                    }
                 },
             }
-            reconstructed.extend_from_slice(&line);
+            kernel(y, &line);
             core::mem::swap(&mut line, &mut prev_line);
             source = next;
         }
+        Ok(())
+    }
 
-        // fix bit depth less than 8
-        if self.info.bit_depth < BitDepth::Bpp8 {
-            let mut fixed =
-                Vec::with_capacity(self.info.width as usize * self.info.height as usize);
-            match self.info.bit_depth {
-                BitDepth::Bpp1 => {
-                    let mut iter = reconstructed.iter();
-                    let iter = &mut iter;
-                    let w8 = self.info.width as usize / 8;
-                    let w8r = self.info.width as usize & 7;
-                    for _y in 0..self.info.height as usize {
-                        for &byte in iter.take(w8) {
-                            for i in (0..8).rev() {
-                                fixed.push((byte >> i) & 0x01);
-                            }
-                        }
-                        if w8r > 0 {
-                            let byte = iter.next().unwrap();
-                            for i in (0..w8r).rev() {
-                                fixed.push((byte >> i) & 0x01);
-                            }
-                        }
+    /// Appends the full precision samples of a reconstructed line for 16-bit images.
+    #[cfg(feature = "alloc")]
+    fn unpack_line16(&self, line: &[u8], output: &mut Vec<u16>) {
+        if self.info.bit_depth == BitDepth::Bpp16 {
+            output.extend(
+                line.chunks_exact(2)
+                    .map(|sample| u16::from_be_bytes([sample[0], sample[1]])),
+            );
+        }
+    }
+
+    /// Expands a reconstructed line to one sample per byte.
+    ///
+    /// A short line leaves the rest of the output untouched instead of panicking,
+    /// although `reconstruct` already rejects an image stream that is too short.
+    #[cfg(feature = "alloc")]
+    fn unpack_line(&self, line: &[u8], output: &mut [u8]) {
+        match self.info.bit_depth {
+            BitDepth::Bpp8 => {
+                output.copy_from_slice(line);
+                return;
+            }
+            BitDepth::Bpp16 => {
+                // keep the most significant byte of each sample
+                for (output, sample) in output.iter_mut().zip(line.chunks_exact(2)) {
+                    *output = sample[0];
+                }
+                return;
+            }
+            _ => {}
+        }
+        // grayscale samples are scaled to the full 8-bit range, but indices are kept as is
+        let scale = if self.info.image_type == ImageType::Grayscale {
+            (0xFF / self.info.bit_depth.max_value()) as u8
+        } else {
+            1
+        };
+        let mut output = output.iter_mut();
+        let mut push = |value: u8| {
+            if let Some(p) = output.next() {
+                *p = value * scale;
+            }
+        };
+        match self.info.bit_depth {
+            BitDepth::Bpp1 => {
+                let w8 = self.info.width as usize / 8;
+                let w8r = self.info.width as usize & 7;
+                for &byte in line.iter().take(w8) {
+                    for i in (0..8).rev() {
+                        push((byte >> i) & 0x01);
                     }
                 }
-                BitDepth::Bpp2 => {
-                    let mut iter = reconstructed.iter();
-                    let iter = &mut iter;
-                    let w4 = self.info.width as usize / 4;
-                    let w4r = self.info.width as usize & 3;
-                    for _y in 0..self.info.height as usize {
-                        for &byte in iter.take(w4) {
-                            for i in (0..4).rev() {
-                                fixed.push((byte >> (i * 2)) & 0x03);
-                            }
-                        }
-                        if w4r > 0 {
-                            let byte = iter.next().unwrap();
-                            for i in (0..w4r).rev() {
-                                fixed.push((byte >> (i * 2)) & 0x03);
-                            }
-                        }
+                if w8r > 0
+                    && let Some(&byte) = line.get(w8)
+                {
+                    for i in (8 - w8r..8).rev() {
+                        push((byte >> i) & 0x01);
                     }
                 }
-                BitDepth::Bpp4 => {
-                    let mut iter = reconstructed.iter();
-                    let iter = &mut iter;
-                    let w2 = self.info.width as usize / 2;
-                    let w2r = self.info.width as usize & 1;
-                    for _y in 0..self.info.height as usize {
-                        for &byte in iter.take(w2) {
-                            for i in (0..2).rev() {
-                                fixed.push((byte >> (i * 4)) & 0x0f);
-                            }
-                        }
-                        if w2r > 0 {
-                            let byte = iter.next().unwrap();
-                            for i in (0..w2r).rev() {
-                                fixed.push((byte >> (i * 4)) & 0x0f);
-                            }
-                        }
+            }
+            BitDepth::Bpp2 => {
+                let w4 = self.info.width as usize / 4;
+                let w4r = self.info.width as usize & 3;
+                for &byte in line.iter().take(w4) {
+                    for i in (0..4).rev() {
+                        push((byte >> (i * 2)) & 0x03);
                     }
                 }
-                BitDepth::Bpp8 => {
-                    unreachable!()
+                if w4r > 0
+                    && let Some(&byte) = line.get(w4)
+                {
+                    for i in (4 - w4r..4).rev() {
+                        push((byte >> (i * 2)) & 0x03);
+                    }
                 }
             }
-            reconstructed = fixed;
-        }
-
-        // pallete check
-        if self.info.image_type == ImageType::Indexed {
-            let Some(palette) = palette.as_ref() else {
-                return Err(DecodeError::InvalidData);
-            };
-            let max_index = reconstructed.iter().copied().max().unwrap() as usize;
-            if palette.len() > 256 || max_index >= palette.len() {
-                return Err(DecodeError::InvalidData);
+            BitDepth::Bpp4 => {
+                let w2 = self.info.width as usize / 2;
+                let w2r = self.info.width as usize & 1;
+                for &byte in line.iter().take(w2) {
+                    for i in (0..2).rev() {
+                        push((byte >> (i * 4)) & 0x0f);
+                    }
+                }
+                if w2r > 0
+                    && let Some(&byte) = line.get(w2)
+                {
+                    for i in (2 - w2r..2).rev() {
+                        push((byte >> (i * 4)) & 0x0f);
+                    }
+                }
+            }
+            BitDepth::Bpp8 | BitDepth::Bpp16 => {
+                unreachable!()
             }
         }
+    }
+}
 
-        // return the image data
-        Ok(ImageData {
-            info: self.info,
-            palette: palette.unwrap_or_default(),
-            data: reconstructed,
-        })
+impl<'a> TryFrom<&'a [u8]> for PngDecoder<'a> {
+    type Error = DecodeError;
+
+    #[inline]
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+/// Allocates a zeroed buffer, returning an error instead of aborting if the memory is not available.
+#[cfg(feature = "alloc")]
+fn try_alloc<T: Clone + Default>(len: usize) -> Result<Vec<T>, DecodeError> {
+    let mut vec = Vec::new();
+    vec.try_reserve_exact(len)
+        .map_err(|_| DecodeError::OutOfMemory)?;
+    vec.resize(len, T::default());
+    Ok(vec)
+}
+
+/// Validates the zlib header (CMF and FLG) of a compressed stream.
+///
+/// PNG only allows deflate (CM = 8) with a window size of up to 32K and no preset dictionary.
+#[cfg(feature = "alloc")]
+pub(crate) fn check_zlib_header(data: &[u8]) -> Result<(), DecodeError> {
+    let [cmf, flg, ..] = *data else {
+        return Err(DecodeError::InvalidZlibHeader);
+    };
+    if !(cmf as u16 * 256 + flg as u16).is_multiple_of(31)
+        || cmf & 0x0F != 8
+        || cmf >> 4 > 7
+        || flg & 0x20 != 0
+    {
+        return Err(DecodeError::InvalidZlibHeader);
     }
+    Ok(())
+}
+
+/// Chunks read before the first IDAT chunk
+#[cfg(feature = "alloc")]
+#[derive(Default)]
+struct HeaderChunks {
+    palette: Option<Vec<RGB888>>,
+    transparency: Option<Vec<u8>>,
+    background: Option<RGBA8888>,
 }
 
 pub struct ChunksInner<'a> {
     iter: slice::Iter<'a, u8>,
+    implicit_iend: bool,
 }
 
 impl<'a> ChunksInner<'a> {
@@ -527,22 +1367,32 @@ impl<'a> ChunksInner<'a> {
 
     pub fn peek_chunk(&self) -> Result<PngChunk<'a>, DecodeError> {
         let slice = self.iter.as_slice();
+        if slice.is_empty() && self.implicit_iend {
+            return Ok(PngChunk::IEND);
+        }
         if slice.len() < 12 {
             return Err(DecodeError::InvalidData);
         }
         let (length, next) = slice.split_at(4);
-        let length = Be32(length.try_into().unwrap()).as_u32() as usize;
+        let length = Be32(length.try_into().unwrap()).as_u32();
         let (chunk_type, next) = next.split_at(4);
         let chunk_type = FourCC(chunk_type.try_into().unwrap());
         if !chunk_type.is_valid() {
             return Err(DecodeError::InvalidData);
         }
-        let Some((data, next)) = next.split_at_checked(length) else {
+        // The length is limited to 2^31-1, and the whole chunk must fit in the remaining bytes
+        // before anything is sliced, so a huge length never overflows.
+        if length > i32::MAX as u32 {
             return Err(DecodeError::InvalidData);
-        };
-        if slice.len() < length + 12 {
+        }
+        let length = length as usize;
+        if length
+            .checked_add(12)
+            .is_none_or(|chunk_len| slice.len() < chunk_len)
+        {
             return Err(DecodeError::InvalidData);
         }
+        let (data, next) = next.split_at(length);
         let crc = Be32(next[..4].try_into().unwrap()).as_u32();
 
         Ok(PngChunk {
@@ -552,7 +1402,22 @@ impl<'a> ChunksInner<'a> {
         })
     }
 
+    /// Returns the available data of an IDAT chunk cut off by the end of the file.
+    #[cfg(feature = "alloc")]
+    fn partial_idat(&self) -> Option<&'a [u8]> {
+        let slice = self.iter.as_slice();
+        if slice.len() <= 8 || &slice[4..8] != b"IDAT" {
+            return None;
+        }
+        let length = Be32(slice[..4].try_into().unwrap()).as_u32() as usize;
+        let data = &slice[8..];
+        (data.len() < length.saturating_add(4)).then(|| &data[..data.len().min(length)])
+    }
+
     /// Look for IDAT chunks and merge buffers if necessary
+    ///
+    /// The data of a single IDAT chunk is borrowed from the input without copying.
+    #[cfg(feature = "alloc")]
     pub fn get_idat_chunks(&mut self, skip_plte: bool) -> Result<Cow<'a, [u8]>, DecodeError> {
         let mut data = Option::<Cow<'a, [u8]>>::None;
         if !skip_plte {
@@ -605,6 +1470,23 @@ pub struct Chunks<'a> {
     inner: ChunksInner<'a>,
 }
 
+impl<'a> Chunks<'a> {
+    /// Advances to the next chunk of the specified type and returns it.
+    ///
+    /// Returns `None` if the IEND chunk is reached first.
+    pub fn skip_to(&mut self, target: FourCC) -> Result<Option<PngChunk<'a>>, DecodeError> {
+        loop {
+            let chunk = self.next_chunk()?;
+            if chunk.is_iend() {
+                return Ok(None);
+            }
+            if chunk.chunk_type() == target {
+                return Ok(Some(chunk));
+            }
+        }
+    }
+}
+
 impl<'a> Deref for Chunks<'a> {
     type Target = ChunksInner<'a>;
 
@@ -638,11 +1520,23 @@ pub struct PngChunk<'a> {
 }
 
 impl<'a> PngChunk<'a> {
+    /// The IEND chunk that stands in for a missing one in lenient mode
+    const IEND: Self = Self {
+        chunk_type: FourCC::IEND,
+        data: &[],
+        crc: 0xAE42_6082,
+    };
+
     #[inline]
     pub const fn len(&self) -> usize {
         self.data.len()
     }
 
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     #[inline]
     pub const fn chunk_type(&self) -> FourCC {
         self.chunk_type
@@ -658,10 +1552,39 @@ impl<'a> PngChunk<'a> {
         matches!(self.chunk_type, FourCC::IEND)
     }
 
+    #[inline]
+    pub const fn is_critical(&self) -> bool {
+        self.chunk_type.is_critical()
+    }
+
+    #[inline]
+    pub const fn is_ancillary(&self) -> bool {
+        self.chunk_type.is_ancillary()
+    }
+
+    #[inline]
+    pub const fn is_safe_to_copy(&self) -> bool {
+        self.chunk_type.is_safe_to_copy()
+    }
+
     #[inline]
     pub fn data(&self) -> &'a [u8] {
         self.data
     }
+
+    /// Returns `true` if the stored CRC matches the CRC computed over the chunk type and the chunk data.
+    #[inline]
+    pub fn crc_matches_computed(&self) -> bool {
+        self.crc == self.computed_crc()
+    }
+
+    /// Computes the CRC over the chunk type and the chunk data.
+    fn computed_crc(&self) -> u32 {
+        let mut crc = Crc32::new();
+        crc.update(&self.chunk_type.0);
+        crc.update(self.data);
+        crc.finalize()
+    }
 }
 
 #[repr(transparent)]
@@ -680,6 +1603,22 @@ impl Be32 {
     }
 }
 
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Be16([u8; 2]);
+
+impl Be16 {
+    #[inline]
+    pub const fn from_u16(value: u16) -> Self {
+        Self(value.to_be_bytes())
+    }
+
+    #[inline]
+    pub const fn as_u16(&self) -> u16 {
+        u16::from_be_bytes(self.0)
+    }
+}
+
 #[repr(transparent)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FourCC(pub [u8; 4]);
@@ -693,6 +1632,20 @@ impl FourCC {
     pub const IDAT: Self = Self(*b"IDAT");
 
     pub const IEND: Self = Self(*b"IEND");
+
+    pub const tRNS: Self = Self(*b"tRNS");
+
+    pub const oFFs: Self = Self(*b"oFFs");
+
+    pub const iCCP: Self = Self(*b"iCCP");
+
+    pub const sPLT: Self = Self(*b"sPLT");
+
+    pub const gIFg: Self = Self(*b"gIFg");
+
+    pub const gIFx: Self = Self(*b"gIFx");
+
+    pub const bKGD: Self = Self(*b"bKGD");
 }
 
 impl FourCC {
@@ -746,6 +1699,34 @@ impl FourCC {
     }
 }
 
+impl PartialEq<[u8; 4]> for FourCC {
+    #[inline]
+    fn eq(&self, other: &[u8; 4]) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<&[u8; 4]> for FourCC {
+    #[inline]
+    fn eq(&self, other: &&[u8; 4]) -> bool {
+        self.0 == **other
+    }
+}
+
+impl PartialEq<str> for FourCC {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for FourCC {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FilterType {
     /// ```plain
@@ -787,33 +1768,41 @@ impl FilterType {
             _ => None,
         }
     }
-}
 
-fn average(lhs: u8, rhs: u8) -> u8 {
-    let avg = (lhs as u16 + rhs as u16) >> 1;
-    avg as u8
-}
-
-/// Paeth predictor
-///
-/// Although the specification states that it is unsigned,
-/// here it is calculated as a signed integer because the decoding result differs when calculated without a sign.
-fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
-    let a = left as i32;
-    let b = above as i32;
-    let c = upper_left as i32;
-    let p = a.wrapping_add(b).wrapping_sub(c);
-    let pa = p.abs_diff(a);
-    let pb = p.abs_diff(b);
-    let pc = p.abs_diff(c);
-    if pa <= pb && pa <= pc {
-        a as u8
-    } else if pb <= pc {
-        b as u8
-    } else {
-        c as u8
+    /// Returns the filter type byte that precedes each scanline.
+    #[inline]
+    pub const fn as_u8(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Sub => 1,
+            Self::Up => 2,
+            Self::Average => 3,
+            Self::Paeth => 4,
+        }
     }
 }
 
 #[test]
 fn it_works() {}
+
+/// Header and chunk inspection must work without the `alloc` feature
+#[test]
+fn header_without_alloc() {
+    // 1x1 grayscale image: signature, IHDR, empty IDAT and IEND
+    const PNG: [u8; 57] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x00, 0x3A,
+        0x7E, 0x9B, 0x55, 0x00, 0x00, 0x00, 0x00, 0x49, 0x44, 0x41, 0x54, 0x35, 0xAF, 0x06, 0x1E,
+        0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82,
+    ];
+    let decoder = PngDecoder::new(&PNG).unwrap();
+    assert_eq!(decoder.info().width, 1);
+    assert_eq!(decoder.info().image_type, ImageType::Grayscale);
+    decoder.verify(true).unwrap();
+    let mut chunks = decoder.chunks().unwrap();
+    assert_eq!(chunks.next_chunk().unwrap().chunk_type(), FourCC::IDAT);
+    assert!(chunks.next_chunk().unwrap().is_iend());
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests;
@@ -0,0 +1,81 @@
+//! Hooks for observing the decoding process
+
+use crate::{FilterType, FourCC};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Phases of decoding reported to a [`DecodeObserver`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodePhase {
+    /// Decompression of the concatenated IDAT data
+    Inflate,
+    /// Reconstruction of the filtered scanlines, including the expansion to one sample per byte
+    Reconstruct,
+}
+
+/// Receives progress reports from [`PngDecoder::decode_with_observer`](crate::PngDecoder::decode_with_observer).
+///
+/// Since this crate is `no_std`, no timing is measured,
+/// but an implementation can take timestamps between `phase_started` and `phase_finished`.
+/// All methods do nothing by default.
+pub trait DecodeObserver {
+    /// Called before a phase starts.
+    #[inline]
+    fn phase_started(&mut self, phase: DecodePhase) {
+        let _ = phase;
+    }
+
+    /// Called after a phase has finished, with the number of bytes it consumed and produced.
+    #[inline]
+    fn phase_finished(&mut self, phase: DecodePhase, input_bytes: usize, output_bytes: usize) {
+        let _ = (phase, input_bytes, output_bytes);
+    }
+
+    /// Called for each chunk in order of appearance, starting with IHDR and ending with IEND,
+    /// with the length of the chunk data.
+    #[inline]
+    fn chunk(&mut self, chunk_type: FourCC, len: usize) {
+        let _ = (chunk_type, len);
+    }
+
+    /// Called for each scanline with its filter type, before the scanlines are reconstructed.
+    #[inline]
+    fn scanline_filter(&mut self, y: usize, filter_type: FilterType) {
+        let _ = (y, filter_type);
+    }
+}
+
+/// Observer that ignores all reports
+impl DecodeObserver for () {}
+
+/// Observer that collects the type and data length of each chunk
+#[cfg(feature = "alloc")]
+impl DecodeObserver for Vec<(FourCC, usize)> {
+    #[inline]
+    fn chunk(&mut self, chunk_type: FourCC, len: usize) {
+        self.push((chunk_type, len));
+    }
+}
+
+/// Statistics collected by [`PngDecoder::decode_with_stats`](crate::PngDecoder::decode_with_stats)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    /// Number of scanlines for each filter type, indexed by the filter type byte
+    pub filter_counts: [usize; 5],
+}
+
+impl DecodeStats {
+    /// Returns the number of scanlines that used the specified filter type.
+    #[inline]
+    pub fn filter_count(&self, filter_type: FilterType) -> usize {
+        self.filter_counts[filter_type.as_u8() as usize]
+    }
+}
+
+impl DecodeObserver for DecodeStats {
+    #[inline]
+    fn scanline_filter(&mut self, _y: usize, filter_type: FilterType) {
+        self.filter_counts[filter_type.as_u8() as usize] += 1;
+    }
+}
@@ -0,0 +1,2467 @@
+use super::*;
+use alloc::vec;
+
+/// Appends a chunk with a valid CRC
+fn push_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(chunk_type);
+    out.extend_from_slice(data);
+    let crc = Crc32::checksum(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps the data in a zlib stream made of stored blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8);
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    out
+}
+
+fn ihdr(width: u32, height: u32, bit_depth: u8, color_type: u8) -> [u8; 13] {
+    let mut ihdr = [0; 13];
+    ihdr[0..4].copy_from_slice(&width.to_be_bytes());
+    ihdr[4..8].copy_from_slice(&height.to_be_bytes());
+    ihdr[8] = bit_depth;
+    ihdr[9] = color_type;
+    ihdr
+}
+
+/// Builds a PNG file from the filtered scanlines (including the filter type bytes)
+fn make_png(
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    pre_idat: &[(&[u8; 4], &[u8])],
+    scanlines: &[u8],
+) -> Vec<u8> {
+    let mut png = PNG_SIGNATURE.to_vec();
+    push_chunk(
+        &mut png,
+        b"IHDR",
+        &ihdr(width, height, bit_depth, color_type),
+    );
+    for (chunk_type, data) in pre_idat {
+        push_chunk(&mut png, chunk_type, data);
+    }
+    push_chunk(&mut png, b"IDAT", &zlib_stored(scanlines));
+    push_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+#[test]
+fn first_row_filters_use_zero_prev_line() {
+    let png = make_png(3, 1, 8, 0, &[], &[3, 10, 20, 30]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), &[10, 25, 42]);
+
+    let png = make_png(3, 1, 8, 0, &[], &[2, 10, 20, 30]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), &[10, 20, 30]);
+}
+
+#[test]
+fn decode_into_array() {
+    let palette = [0, 0, 0, 255, 255, 255, 255, 0, 0];
+    let png = make_png(2, 2, 4, 3, &[(b"PLTE", &palette)], &[0, 0x12, 0, 0x01]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let mut buf = [0u8; 4];
+    decoder.decode_into_array(&mut buf).unwrap();
+    assert_eq!(buf, [1, 2, 0, 1]);
+    assert_eq!(decoder.decode().unwrap().raw_data(), &buf);
+
+    let mut wrong_size = [0u8; 8];
+    assert_eq!(
+        decoder.decode_into_array(&mut wrong_size),
+        Err(DecodeError::InvalidData)
+    );
+}
+
+#[test]
+fn image_data_clone_and_debug() {
+    let png = make_png(2, 1, 8, 2, &[], &[0, 1, 2, 3, 4, 5, 6]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let cloned = decoded.clone();
+    assert_eq!(cloned.raw_data(), decoded.raw_data());
+    assert_eq!(cloned.info(), decoded.info());
+
+    let debug = format!("{:?}", decoded);
+    assert!(debug.contains("data_len: 6"));
+}
+
+#[test]
+fn gray_alpha_planes() {
+    let png = make_png(3, 1, 8, 4, &[], &[0, 10, 255, 20, 128, 30, 0]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let (gray, alpha) = decoded.gray_alpha_planes().unwrap();
+    assert_eq!(gray, [10, 20, 30]);
+    assert_eq!(alpha, [255, 128, 0]);
+
+    let png = make_png(1, 1, 8, 0, &[], &[0, 10]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert!(decoded.gray_alpha_planes().is_none());
+}
+
+#[test]
+fn raw_color_type_and_bit_depth() {
+    let png = make_png(1, 1, 8, 6, &[], &[0, 1, 2, 3, 4]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.info().raw_color_type(), 6);
+    assert_eq!(decoder.info().raw_bit_depth(), 8);
+    assert_eq!(decoder.info().image_type, ImageType::RGBA);
+}
+
+#[test]
+fn sub_byte_samples_with_odd_width() {
+    let palette = [0u8; 16 * 3];
+    let png = make_png(3, 1, 4, 3, &[(b"PLTE", &palette)], &[0, 0x12, 0x30]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), &[1, 2, 3]);
+
+    let png = make_png(
+        3,
+        1,
+        2,
+        3,
+        &[(b"PLTE", &palette[..4 * 3])],
+        &[0, 0b0110_1100],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), &[1, 2, 3]);
+
+    let png = make_png(
+        3,
+        1,
+        1,
+        3,
+        &[(b"PLTE", &palette[..2 * 3])],
+        &[0, 0b1010_0000],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), &[1, 0, 1]);
+}
+
+#[test]
+fn scan_for_signature() {
+    let png = make_png(1, 1, 8, 0, &[], &[0, 42]);
+    let mut junk = vec![0xEF, 0xBB, 0xBF, 0x00];
+    junk.extend_from_slice(&png);
+
+    assert_eq!(PngDecoder::find_signature(&junk), Some(4));
+    assert!(PngDecoder::new(&junk).is_err());
+    let decoded = PngDecoder::scan(&junk).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), &[42]);
+}
+
+#[test]
+fn into_rgba() {
+    let palette = [255, 0, 0, 0, 0, 255];
+    let png = make_png(2, 1, 8, 3, &[(b"PLTE", &palette)], &[0, 1, 0]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let rgba = decoded.into_rgba();
+    assert_eq!(rgba.info().image_type, ImageType::RGBA);
+    assert_eq!(rgba.info().width, 2);
+    assert!(rgba.palette().is_none());
+    assert_eq!(rgba.raw_data(), &[0, 0, 255, 255, 255, 0, 0, 255]);
+}
+
+#[test]
+fn image_offset() {
+    let mut offs = Vec::new();
+    offs.extend_from_slice(&(-10i32).to_be_bytes());
+    offs.extend_from_slice(&(-20i32).to_be_bytes());
+    offs.push(1);
+    let png = make_png(1, 1, 8, 0, &[(b"oFFs", &offs)], &[0, 0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(
+        decoder.offset(),
+        Ok(Some(ImageOffset {
+            x: -10,
+            y: -20,
+            unit: OffsetUnit::Micrometer
+        }))
+    );
+
+    let png = make_png(1, 1, 8, 0, &[(b"oFFs", &offs[..8])], &[0, 0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.offset(), Err(DecodeError::InvalidData));
+
+    let png = make_png(1, 1, 8, 0, &[], &[0, 0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.offset(), Ok(None));
+}
+
+#[test]
+fn crc32() {
+    let mut crc = Crc32::new();
+    crc.update(b"IEND");
+    crc.update(&[]);
+    assert_eq!(crc.finalize(), 0xAE42_6082);
+
+    let mut crc = Crc32::new();
+    crc.update(b"IH");
+    crc.update(b"DR");
+    assert_eq!(crc.finalize(), Crc32::checksum(b"IHDR"));
+}
+
+#[test]
+fn unsupported_ihdr_methods() {
+    for (index, expected) in [
+        (10, DecodeError::UnsupportedCompressionMethod(1)),
+        (11, DecodeError::UnsupportedFilterMethod(1)),
+        (12, DecodeError::UnsupportedInterlaceMethod(1)),
+    ] {
+        let mut header = ihdr(1, 1, 8, 0);
+        header[index] = 1;
+        let mut png = PNG_SIGNATURE.to_vec();
+        push_chunk(&mut png, b"IHDR", &header);
+        push_chunk(&mut png, b"IEND", &[]);
+        assert_eq!(PngDecoder::new(&png).err(), Some(expected));
+    }
+}
+
+#[test]
+fn map_pixels() {
+    let invert = |color: color::RGBA8888| {
+        color::RGBA8888::from_rgba(255 - color.r(), 255 - color.g(), 255 - color.b(), color.a())
+    };
+
+    let png = make_png(2, 1, 8, 2, &[], &[0, 255, 0, 0, 10, 20, 30]);
+    let mut decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    decoded.map_pixels(invert);
+    assert_eq!(decoded.raw_data(), &[0, 255, 255, 245, 235, 225]);
+
+    let palette = [255, 0, 0, 0, 0, 0];
+    let png = make_png(2, 1, 8, 3, &[(b"PLTE", &palette)], &[0, 0, 1]);
+    let mut decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    decoded.map_pixels(invert);
+    assert_eq!(decoded.raw_data(), &[0, 1]);
+    assert_eq!(
+        decoded.palette().unwrap(),
+        &[
+            color::RGB888::new(0, 255, 255),
+            color::RGB888::new(255, 255, 255)
+        ]
+    );
+
+    // the full precision samples follow the rewritten pixels
+    let png = make_png(2, 1, 16, 0, &[], &[0, 0x12, 0x34, 0xFE, 0xDC]);
+    let mut decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    decoded.map_pixels(|color| {
+        if color.r() == 0x12 {
+            color::RGBA8888::from_gray(0xFF)
+        } else {
+            color
+        }
+    });
+    assert_eq!(decoded.raw_data(), &[0xFF, 0xFE]);
+    assert_eq!(decoded.raw_data_u16(), Some(&[0xFFFF, 0xFEDC][..]));
+    assert_eq!(decoded.to_rgba_f32()[0], 1.0);
+}
+
+#[test]
+fn palette_without_decoding() {
+    let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+    let png = make_png(3, 1, 8, 3, &[(b"PLTE", &palette)], &[0, 0, 1, 2]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let decoded = decoder.decode().unwrap();
+    assert_eq!(decoder.palette().unwrap().as_deref(), decoded.palette());
+
+    let png = make_png(1, 1, 8, 0, &[], &[0, 0]);
+    assert_eq!(PngDecoder::new(&png).unwrap().palette(), Ok(None));
+}
+
+#[test]
+fn rgb16_stride_and_bpp() {
+    let row: [u8; 12] = [
+        0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0x11, 0x11, 0x22, 0x22, 0x33, 0x33,
+    ];
+    let mut scanlines = vec![1];
+    for (i, &x) in row.iter().enumerate() {
+        let left = if i >= 6 { row[i - 6] } else { 0 };
+        scanlines.push(x.wrapping_sub(left));
+    }
+    scanlines.push(2);
+    scanlines.extend_from_slice(&[0; 12]);
+
+    let png = make_png(2, 2, 16, 2, &[], &scanlines);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.info().bit_depth, BitDepth::Bpp16);
+    let decoded = decoder.decode().unwrap();
+    assert_eq!(
+        decoded.raw_data(),
+        &[
+            0x12, 0x56, 0x9A, 0x11, 0x22, 0x33, 0x12, 0x56, 0x9A, 0x11, 0x22, 0x33
+        ]
+    );
+}
+
+#[test]
+fn aspect_ratio() {
+    let scanlines = [[0u8; 17]; 9].concat();
+    let png = make_png(16, 9, 8, 0, &[], &scanlines);
+    let info = *PngDecoder::new(&png).unwrap().info();
+    assert!((info.aspect_ratio() - 16.0 / 9.0).abs() < f32::EPSILON);
+    assert!(info.is_landscape());
+    assert!(!info.is_portrait());
+    assert!(!info.is_square());
+}
+
+#[test]
+fn output_channels() {
+    assert_eq!(ImageType::Indexed.n_channels(), 1);
+    assert_eq!(ImageType::Indexed.output_channels(false), 3);
+    assert_eq!(ImageType::Indexed.output_channels(true), 4);
+    assert_eq!(ImageType::Grayscale.output_channels(false), 1);
+    assert_eq!(ImageType::GrayscaleAlpha.output_channels(true), 2);
+    assert_eq!(ImageType::RGBA.output_channels(false), 3);
+}
+
+#[test]
+fn crop() {
+    let mut scanlines = Vec::new();
+    for y in 0..4u8 {
+        scanlines.push(0);
+        scanlines.extend((0..4u8).map(|x| y * 16 + x));
+    }
+    let png = make_png(4, 4, 8, 0, &[], &scanlines);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+
+    let cropped = decoded.crop(1, 1, 2, 2).unwrap();
+    assert_eq!(cropped.info().width, 2);
+    assert_eq!(cropped.info().height, 2);
+    assert_eq!(cropped.info().image_type, ImageType::Grayscale);
+    assert_eq!(cropped.raw_data(), &[17, 18, 33, 34]);
+
+    assert!(decoded.crop(3, 0, 2, 1).is_err());
+    assert!(decoded.crop(0, 0, 0, 1).is_err());
+}
+
+#[test]
+fn display_names() {
+    assert_eq!(ImageType::RGBA.to_string(), "Truecolor with alpha");
+    assert_eq!(ImageType::Indexed.to_string(), "Indexed-color");
+    assert_eq!(BitDepth::Bpp8.to_string(), "8-bit");
+    assert_eq!(BitDepth::Bpp16.to_string(), "16-bit");
+}
+
+#[test]
+fn to_gray_bytes() {
+    let png = make_png(2, 1, 8, 0, &[], &[0, 10, 20]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let gray = decoded.to_gray_bytes();
+    assert!(matches!(gray, Cow::Borrowed(_)));
+    assert_eq!(&*gray, &[10, 20]);
+
+    let png = make_png(2, 1, 8, 2, &[], &[0, 255, 255, 255, 255, 0, 0]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let gray = decoded.to_gray_bytes();
+    assert!(matches!(gray, Cow::Owned(_)));
+    assert_eq!(&*gray, &[255, 76]);
+}
+
+#[test]
+fn palette_size_against_bit_depth() {
+    // 4-bit indices can address only 16 entries
+    let palette = [0u8; 17 * 3];
+    let png = make_png(2, 1, 4, 3, &[(b"PLTE", &palette)], &[0, 0x01]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.decode().err(), Some(DecodeError::InvalidPalette));
+
+    // index 2 is out of the palette
+    let palette = [0u8; 2 * 3];
+    let png = make_png(2, 1, 4, 3, &[(b"PLTE", &palette)], &[0, 0x12]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.decode().err(), Some(DecodeError::InvalidPalette));
+
+    let palette = [0u8; 16 * 3];
+    let png = make_png(2, 1, 4, 3, &[(b"PLTE", &palette)], &[0, 0xF0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.decode().unwrap().raw_data(), &[15, 0]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn image_info_serde() {
+    let png = make_png(3, 2, 16, 6, &[], &[0; 2 * (1 + 3 * 8)]);
+    let info = *PngDecoder::new(&png).unwrap().info();
+    let json = serde_json::to_string(&info).unwrap();
+    let restored: ImageInfo = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, info);
+}
+
+#[test]
+fn decode_partial() {
+    // the image stream contains only two of four rows, and IEND is missing
+    let mut png = PNG_SIGNATURE.to_vec();
+    push_chunk(&mut png, b"IHDR", &ihdr(2, 4, 8, 0));
+    push_chunk(&mut png, b"IDAT", &zlib_stored(&[0, 1, 2, 0, 3, 4]));
+
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert!(decoder.decode().is_err());
+    let (decoded, valid_rows) = decoder.decode_partial().unwrap();
+    assert_eq!(valid_rows, 2);
+    assert_eq!(decoded.raw_data(), &[1, 2, 3, 4, 0, 0, 0, 0]);
+
+    // the file ends in the CRC of the last IDAT chunk, after a complete zlib stream
+    png.truncate(png.len() - 2);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert!(decoder.decode().is_err());
+    let (decoded, valid_rows) = decoder.decode_partial().unwrap();
+    assert_eq!(valid_rows, 2);
+    assert_eq!(decoded.raw_data(), &[1, 2, 3, 4, 0, 0, 0, 0]);
+
+    // a zlib stream cut in the middle cannot be decompressed
+    let zlib = zlib_stored(&[0, 1, 2, 0, 3, 4, 0, 5, 6, 0, 7, 8]);
+    let mut png = PNG_SIGNATURE.to_vec();
+    push_chunk(&mut png, b"IHDR", &ihdr(2, 4, 8, 0));
+    push_chunk(&mut png, b"IDAT", &zlib[..zlib.len() - 8]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(
+        decoder.decode_partial().err(),
+        Some(DecodeError::InvalidData)
+    );
+}
+
+#[test]
+fn ihdr_methods() {
+    let png = make_png(1, 1, 8, 0, &[], &[0, 0]);
+    let info = *PngDecoder::new(&png).unwrap().info();
+    assert_eq!(info.compression_method, 0);
+    assert_eq!(info.filter_method, 0);
+    assert_eq!(info.interlace_method, 0);
+}
+
+#[test]
+fn decode_rgba_and_rgb() {
+    let png = make_png(2, 1, 8, 0, &[], &[0, 10, 20]);
+    let (info, data) = decode_rgba(&png).unwrap();
+    assert_eq!((info.width, info.height), (2, 1));
+    assert_eq!(data, [10, 10, 10, 255, 20, 20, 20, 255]);
+
+    let (info, data) = decode_rgb(&png).unwrap();
+    assert_eq!(info.image_type, ImageType::Grayscale);
+    assert_eq!(data.len(), 6);
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn into_dynamic_image() {
+    let png = make_png(2, 1, 8, 6, &[], &[0, 1, 2, 3, 4, 5, 6, 7, 8]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let image = decoded.into_dynamic_image();
+    assert_eq!((image.width(), image.height()), (2, 1));
+    assert_eq!(
+        image.as_rgba8().unwrap().as_raw(),
+        &[1, 2, 3, 4, 5, 6, 7, 8]
+    );
+
+    // 16-bit images keep their full precision
+    let png = make_png(2, 1, 16, 0, &[], &[0, 0x12, 0x34, 0xFE, 0xDC]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let image = decoded.into_dynamic_image();
+    assert_eq!(image.as_luma16().unwrap().as_raw(), &[0x1234, 0xFEDC]);
+
+    let png = make_png(1, 1, 16, 2, &[], &[0, 1, 2, 3, 4, 5, 6]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let image = decoded.into_dynamic_image();
+    assert_eq!(
+        image.as_rgb16().unwrap().as_raw(),
+        &[0x0102, 0x0304, 0x0506]
+    );
+
+    // index color format images with an alpha table are expanded to RGBA
+    let palette = [1, 2, 3, 4, 5, 6];
+    let png = make_png(
+        2,
+        1,
+        8,
+        3,
+        &[(b"PLTE", &palette), (b"tRNS", &[0x80])],
+        &[0, 0, 1],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let image = decoded.into_dynamic_image();
+    assert_eq!(
+        image.as_rgba8().unwrap().as_raw(),
+        &[1, 2, 3, 0x80, 4, 5, 6, 0xFF]
+    );
+}
+
+#[test]
+fn chunk_properties() {
+    let png = make_png(1, 1, 8, 0, &[(b"tEXt", b"Title\0pngss")], &[0, 0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let mut chunks = decoder.chunks().unwrap();
+    let text = chunks.next_chunk().unwrap();
+    assert_eq!(text.chunk_type(), FourCC(*b"tEXt"));
+    assert!(!text.is_critical());
+    assert!(text.is_ancillary());
+    assert!(text.is_safe_to_copy());
+    let idat = chunks.next_chunk().unwrap();
+    assert_eq!(idat.chunk_type(), FourCC::IDAT);
+    assert!(idat.is_critical());
+    assert!(!idat.is_safe_to_copy());
+}
+
+#[test]
+fn to_rgb_bytes_dithered() {
+    // a flat 16-bit gray halfway between two 8-bit levels
+    let mut scanlines = Vec::new();
+    for _ in 0..4 {
+        scanlines.push(0);
+        scanlines.extend_from_slice(&0x1080u16.to_be_bytes().repeat(4));
+    }
+    let png = make_png(4, 4, 16, 0, &[], &scanlines);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+
+    let truncated = decoded.to_rgb_bytes();
+    assert!(truncated.iter().all(|&v| v == 0x10));
+    let dithered = decoded.to_rgb_bytes_dithered();
+    assert_eq!(dithered.len(), truncated.len());
+    assert_ne!(&*dithered, &*truncated);
+    assert!(dithered.iter().all(|&v| v == 0x10 || v == 0x11));
+
+    let png = make_png(1, 1, 8, 0, &[], &[0, 42]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(&*decoded.to_rgb_bytes_dithered(), &[42, 42, 42]);
+}
+
+#[test]
+fn four_cc_equality() {
+    let text = FourCC(*b"tEXt");
+    assert!(text == b"tEXt");
+    assert!(text == *b"tEXt");
+    assert!(text == "tEXt");
+    assert!(text != "IDAT");
+    assert!(text != "tEX");
+    assert!(FourCC::IDAT != b"tEXt");
+}
+
+#[test]
+fn public_filters() {
+    assert_eq!(filter::paeth(10, 20, 15), 15);
+    assert_eq!(filter::paeth(100, 30, 30), 100);
+    assert_eq!(filter::paeth(30, 100, 30), 100);
+    assert_eq!(filter::average(255, 254), 254);
+
+    let rows: [[u8; 6]; 2] = [[1, 2, 3, 250, 5, 6], [7, 0, 9, 10, 255, 12]];
+    for filter_type in [
+        FilterType::None,
+        FilterType::Sub,
+        FilterType::Up,
+        FilterType::Average,
+        FilterType::Paeth,
+    ] {
+        let mut scanlines = Vec::new();
+        let mut prev_line = [0u8; 6];
+        for row in rows.iter() {
+            scanlines.push(filter_type.as_u8());
+            filter::filter_line(filter_type, 3, row, &prev_line, &mut scanlines);
+            prev_line = *row;
+        }
+        let png = make_png(2, 2, 8, 2, &[], &scanlines);
+        let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+        assert_eq!(decoded.raw_data(), rows.concat());
+    }
+}
+
+#[test]
+fn from_parts() {
+    let png = make_png(2, 1, 8, 0, &[(b"tEXt", b"Title\0pngss")], &[0, 10, 20]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let info = *decoder.info();
+    let mut chunks = decoder.chunks().unwrap();
+    assert_eq!(chunks.next_chunk().unwrap().chunk_type(), "tEXt");
+
+    let decoded = PngDecoder::from_parts(info, chunks)
+        .unwrap()
+        .decode()
+        .unwrap();
+    assert_eq!(decoded.raw_data(), &[10, 20]);
+
+    // inconsistent image information is rejected instead of panicking later
+    let info = ImageInfo {
+        image_type: ImageType::RGB,
+        bit_depth: BitDepth::Bpp1,
+        ..info
+    };
+    assert_eq!(
+        PngDecoder::from_parts(info, decoder.chunks().unwrap()).err(),
+        Some(DecodeError::InvalidBitDepth {
+            color_type: 2,
+            bit_depth: 1
+        })
+    );
+}
+
+#[test]
+fn first_row_average_and_paeth_multi_channel() {
+    // the previous line of the first row is all zeros, so Paeth acts as Sub and Average as half of Sub
+    let png = make_png(2, 1, 8, 2, &[], &[4, 1, 2, 3, 1, 1, 1]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), &[1, 2, 3, 2, 3, 4]);
+
+    let png = make_png(2, 1, 8, 2, &[], &[3, 10, 20, 30, 1, 1, 1]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), &[10, 20, 30, 6, 11, 16]);
+}
+
+#[test]
+fn row_accessor() {
+    let png = make_png(
+        2,
+        2,
+        8,
+        2,
+        &[],
+        &[0, 1, 2, 3, 4, 5, 6, 0, 7, 8, 9, 10, 11, 12],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.row(0), Some(&decoded.raw_data()[..6]));
+    assert_eq!(decoded.row(1), Some(&[7, 8, 9, 10, 11, 12][..]));
+    assert_eq!(decoded.row(2), None);
+}
+
+#[test]
+fn sub_8bit_grayscale_is_scaled() {
+    let png = make_png(
+        16,
+        1,
+        4,
+        0,
+        &[],
+        &[0, 0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let expected: Vec<u8> = (0..16).map(|v| v * 17).collect();
+    assert_eq!(decoded.raw_data(), expected);
+
+    let png = make_png(4, 1, 2, 0, &[], &[0, 0b00_01_10_11]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), &[0, 85, 170, 255]);
+
+    // indices are not scaled
+    let palette = [0u8; 16 * 3];
+    let png = make_png(2, 1, 4, 3, &[(b"PLTE", &palette)], &[0, 0xF1]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), &[15, 1]);
+}
+
+#[test]
+fn verify() {
+    let png = make_png(1, 1, 8, 0, &[(b"tEXt", b"Title\0pngss")], &[0, 0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.verify(true), Ok(()));
+
+    // corrupt the last byte of the tEXt data
+    let mut corrupted = png.clone();
+    corrupted[33 + 8 + 10] ^= 0xFF;
+    let decoder = PngDecoder::new(&corrupted).unwrap();
+    assert_eq!(decoder.verify(false), Ok(()));
+    assert_eq!(decoder.verify(true), Err(DecodeError::CrcMismatch));
+
+    // corrupt the IHDR CRC
+    let mut corrupted = png.clone();
+    corrupted[32] ^= 0xFF;
+    let decoder = PngDecoder::new(&corrupted).unwrap();
+    assert_eq!(decoder.verify(true), Err(DecodeError::CrcMismatch));
+
+    // an indexed image without PLTE
+    let png = make_png(1, 1, 8, 3, &[], &[0, 0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.verify(true), Err(DecodeError::InvalidData));
+}
+
+#[test]
+fn indexed_transparency() {
+    let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+    let png = make_png(
+        3,
+        1,
+        8,
+        3,
+        &[(b"PLTE", &palette), (b"tRNS", &[0, 128])],
+        &[0, 0, 1, 2],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.transparency(), Some(&[0, 128][..]));
+    assert_eq!(
+        &*decoded.to_rgba_bytes(),
+        &[255, 0, 0, 0, 0, 255, 0, 128, 0, 0, 255, 255]
+    );
+
+    // the alpha table must not be longer than the palette
+    let png = make_png(
+        1,
+        1,
+        8,
+        3,
+        &[(b"PLTE", &palette[..3]), (b"tRNS", &[0, 0])],
+        &[0, 0],
+    );
+    assert!(PngDecoder::new(&png).unwrap().decode().is_err());
+}
+
+#[test]
+fn unknown_chunks() {
+    let png = make_png(
+        1,
+        1,
+        8,
+        0,
+        &[
+            (b"prVt", &[1, 2, 3]),
+            (b"oFFs", &[0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            (b"prVt", &[]),
+        ],
+        &[0, 0],
+    );
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.unknown_chunks().unwrap(), [FourCC(*b"prVt")]);
+}
+
+#[test]
+fn blend_over() {
+    use color::RGBA8888;
+
+    let red = RGBA8888::from_rgba(255, 0, 0, 128);
+    let blue = RGBA8888::from_rgb(0, 0, 255);
+    assert_eq!(red.blend_over(blue), RGBA8888::from_rgba(128, 0, 127, 255));
+
+    // translucent over translucent keeps straight alpha
+    let dst = RGBA8888::from_rgba(0, 0, 255, 128);
+    assert_eq!(red.blend_over(dst), RGBA8888::from_rgba(170, 0, 85, 192));
+
+    let clear = RGBA8888::from_rgba(0, 0, 0, 0);
+    assert_eq!(clear.blend_over(blue), blue);
+    assert_eq!(clear.blend_over(clear), clear);
+}
+
+#[test]
+fn implicit_iend() {
+    let png = make_png(2, 1, 8, 0, &[], &[0, 10, 20]);
+    // IEND is always the last 12 bytes
+    let truncated = &png[..png.len() - 12];
+
+    assert!(PngDecoder::new(truncated).unwrap().decode().is_err());
+
+    let decoder = PngDecoder::new(truncated).unwrap().lenient(true);
+    assert!(decoder.is_lenient());
+    decoder.verify(true).unwrap();
+    assert_eq!(decoder.decode().unwrap().raw_data(), &[10, 20]);
+
+    // a chunk cut in the middle is still an error
+    let broken = &png[..png.len() - 14];
+    assert!(
+        PngDecoder::new(broken)
+            .unwrap()
+            .lenient(true)
+            .decode()
+            .is_err()
+    );
+}
+
+#[test]
+fn indices() {
+    let palette = [0, 0, 0, 85, 85, 85, 170, 170, 170, 255, 255, 255];
+    let png = make_png(
+        5,
+        1,
+        2,
+        3,
+        &[(b"PLTE", &palette)],
+        &[0, 0b00_01_10_11, 0b10_000000],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.indices(), Some(&[0, 1, 2, 3, 2][..]));
+
+    let png = make_png(1, 1, 8, 0, &[], &[0, 0]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.indices(), None);
+}
+
+#[test]
+fn average_color() {
+    let png = make_png(2, 2, 8, 0, &[], &[0, 0, 0, 0, 255, 255]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(
+        decoded.average_color(),
+        color::RGBA8888::from_rgba(128, 128, 128, 255)
+    );
+}
+
+#[test]
+fn try_from_slice() {
+    fn decode(bytes: &[u8]) -> Result<ImageData, DecodeError> {
+        let decoder: PngDecoder = bytes.try_into()?;
+        decoder.decode()
+    }
+
+    let png = make_png(2, 1, 8, 0, &[], &[0, 10, 20]);
+    assert_eq!(decode(&png).unwrap().raw_data(), &[10, 20]);
+    assert_eq!(decode(&png[1..]).unwrap_err(), DecodeError::InvalidData);
+}
+
+#[test]
+fn icc_profile() {
+    // the size of the sRGB IEC61966-2.1 profile
+    let profile = (0..3144).map(|v| v as u8).collect::<Vec<_>>();
+    let mut iccp = b"sRGB IEC61966-2.1\0\0".to_vec();
+    iccp.extend_from_slice(&zlib_stored(&profile));
+    let png = make_png(1, 1, 8, 0, &[(b"iCCP", &iccp)], &[0, 0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let decompressed = decoder.icc_profile().unwrap().unwrap();
+    assert_eq!(decompressed.len(), 3144);
+    assert_eq!(decompressed, profile);
+    assert!(decoder.unknown_chunks().unwrap().is_empty());
+
+    let png = make_png(1, 1, 8, 0, &[], &[0, 0]);
+    assert_eq!(PngDecoder::new(&png).unwrap().icc_profile().unwrap(), None);
+
+    iccp[18] = 1;
+    let png = make_png(1, 1, 8, 0, &[(b"iCCP", &iccp)], &[0, 0]);
+    assert_eq!(
+        PngDecoder::new(&png).unwrap().icc_profile(),
+        Err(DecodeError::UnsupportedCompressionMethod(1))
+    );
+}
+
+#[test]
+fn rgba_components_byte_order() {
+    use color::{RGBA8888, RGBAComponents8888};
+
+    let color = RGBA8888::from_rgba(0x12, 0x34, 0x56, 0x78);
+    assert_eq!(
+        (color.r(), color.g(), color.b(), color.a()),
+        (0x12, 0x34, 0x56, 0x78)
+    );
+    assert_eq!(unsafe { color.into_inner() }, 0x7856_3412);
+    assert_eq!(color.components().into_array(), [0x12, 0x34, 0x56, 0x78]);
+
+    let gray = RGBA8888::from_gray_alpha(0x9A, 0xBC);
+    assert_eq!(
+        gray.components(),
+        RGBAComponents8888::new(0x9A, 0x9A, 0x9A, 0xBC)
+    );
+}
+
+#[test]
+fn is_opaque() {
+    let png = make_png(2, 1, 8, 6, &[], &[0, 1, 2, 3, 255, 4, 5, 6, 255]);
+    assert!(PngDecoder::new(&png).unwrap().decode().unwrap().is_opaque());
+
+    let png = make_png(2, 1, 8, 0, &[], &[0, 0, 0]);
+    assert!(PngDecoder::new(&png).unwrap().decode().unwrap().is_opaque());
+
+    let png = make_png(2, 1, 8, 6, &[], &[0, 1, 2, 3, 255, 4, 5, 6, 254]);
+    assert!(!PngDecoder::new(&png).unwrap().decode().unwrap().is_opaque());
+
+    // 0xFFFE is not opaque, although its high byte is 0xFF
+    let png = make_png(1, 1, 16, 4, &[], &[0, 0, 0, 0xFF, 0xFE]);
+    assert!(!PngDecoder::new(&png).unwrap().decode().unwrap().is_opaque());
+
+    // only the palette entries in use matter
+    let palette = [0, 0, 0, 255, 255, 255];
+    let png = make_png(1, 1, 8, 3, &[(b"PLTE", &palette), (b"tRNS", &[0])], &[0, 1]);
+    assert!(PngDecoder::new(&png).unwrap().decode().unwrap().is_opaque());
+    let png = make_png(1, 1, 8, 3, &[(b"PLTE", &palette), (b"tRNS", &[0])], &[0, 0]);
+    assert!(!PngDecoder::new(&png).unwrap().decode().unwrap().is_opaque());
+}
+
+#[test]
+fn zlib_header() {
+    let mut stream = zlib_stored(&[0, 10, 20]);
+    // FDICT with a valid FCHECK
+    stream[1] = 0x20;
+    let mut png = Vec::new();
+    png.extend_from_slice(PNG_SIGNATURE);
+    push_chunk(&mut png, b"IHDR", &ihdr(2, 1, 8, 0));
+    push_chunk(&mut png, b"IDAT", &stream);
+    push_chunk(&mut png, b"IEND", &[]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(
+        decoder.decode().unwrap_err(),
+        DecodeError::InvalidZlibHeader
+    );
+    assert_eq!(
+        decoder.decode_partial().unwrap_err(),
+        DecodeError::InvalidZlibHeader
+    );
+
+    assert_eq!(check_zlib_header(&[0x78, 0x01]), Ok(()));
+    // compression method 7
+    assert_eq!(
+        check_zlib_header(&[0x77, 0x09]),
+        Err(DecodeError::InvalidZlibHeader)
+    );
+    // wrong FCHECK
+    assert_eq!(
+        check_zlib_header(&[0x78, 0x02]),
+        Err(DecodeError::InvalidZlibHeader)
+    );
+    assert_eq!(
+        check_zlib_header(&[0x78]),
+        Err(DecodeError::InvalidZlibHeader)
+    );
+}
+
+#[test]
+fn channel_names() {
+    assert_eq!(ImageType::RGBA.channel_names(), ["R", "G", "B", "A"]);
+    assert_eq!(ImageType::Grayscale.channel_names(), ["Gray"]);
+    assert_eq!(ImageType::Indexed.channel_names(), ["Index"]);
+    for image_type in [
+        ImageType::Grayscale,
+        ImageType::GrayscaleAlpha,
+        ImageType::RGB,
+        ImageType::RGBA,
+        ImageType::Indexed,
+    ] {
+        assert_eq!(image_type.channel_names().len(), image_type.n_channels());
+    }
+}
+
+#[test]
+fn to_rgba_f32() {
+    let png = make_png(2, 1, 8, 2, &[], &[0, 255, 0, 255, 0, 0, 0]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(
+        decoded.to_rgba_f32(),
+        [1.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 1.0]
+    );
+
+    let png = make_png(1, 1, 16, 4, &[], &[0, 0xFF, 0xFF, 0x80, 0x00]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let pixel = decoded.to_rgba_f32();
+    assert_eq!(&pixel[..3], [1.0, 1.0, 1.0]);
+    assert_eq!(pixel[3], 0x8000 as f32 / 65535.0);
+}
+
+#[test]
+fn lenient_palette() {
+    let palette = [255, 0, 0, 0, 0, 255, 7];
+    let png = make_png(2, 1, 8, 3, &[(b"PLTE", &palette)], &[0, 0, 1]);
+
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.decode().unwrap_err(), DecodeError::InvalidData);
+    assert!(decoder.verify(false).is_err());
+
+    let decoder = PngDecoder::new(&png).unwrap().lenient(true);
+    decoder.verify(true).unwrap();
+    let decoded = decoder.decode().unwrap();
+    assert_eq!(decoded.palette().unwrap().len(), 2);
+    assert_eq!(&*decoded.to_rgb_bytes(), &[255, 0, 0, 0, 0, 255]);
+}
+
+#[test]
+fn chunks_skip_to() {
+    let palette = [0, 0, 0, 255, 255, 255];
+    let png = make_png(1, 1, 8, 3, &[(b"prVt", &[1]), (b"PLTE", &palette)], &[0, 1]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let mut chunks = decoder.chunks().unwrap();
+    let plte = chunks.skip_to(FourCC::PLTE).unwrap().unwrap();
+    assert_eq!(plte.len(), 6);
+    assert_eq!(chunks.next_chunk().unwrap().chunk_type(), FourCC::IDAT);
+    assert!(chunks.skip_to(FourCC::PLTE).unwrap().is_none());
+}
+
+#[test]
+fn rows() {
+    let png = make_png(2, 3, 8, 2, &[], &[0, 1, 2, 3, 4, 5, 6].repeat(3));
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.rows().count(), 3);
+    assert_eq!(decoded.rows().next().unwrap(), [1, 2, 3, 4, 5, 6]);
+    assert!(
+        decoded
+            .rows()
+            .zip(0..)
+            .all(|(row, y)| Some(row) == decoded.row(y))
+    );
+}
+
+#[test]
+fn invalid_bit_depth() {
+    let png = make_png(1, 1, 16, 3, &[], &[0, 0, 0]);
+    assert_eq!(
+        PngDecoder::new(&png).err(),
+        Some(DecodeError::InvalidBitDepth {
+            color_type: 3,
+            bit_depth: 16
+        })
+    );
+
+    let png = make_png(1, 1, 4, 2, &[], &[0, 0]);
+    assert_eq!(
+        PngDecoder::new(&png).err(),
+        Some(DecodeError::InvalidBitDepth {
+            color_type: 2,
+            bit_depth: 4
+        })
+    );
+
+    // unknown color type
+    let png = make_png(1, 1, 8, 5, &[], &[0, 0]);
+    assert_eq!(
+        PngDecoder::new(&png).err(),
+        Some(DecodeError::UnsupportedFormat)
+    );
+}
+
+#[test]
+fn chunk_crc_matches_computed() {
+    let mut png = make_png(2, 1, 8, 0, &[], &[0, 10, 20]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert!(
+        decoder
+            .chunks()
+            .unwrap()
+            .all(|chunk| chunk.crc_matches_computed())
+    );
+
+    // tamper with the first byte of the IDAT data, right after the IHDR chunk
+    png[8 + 25 + 8] ^= 1;
+    let decoder = PngDecoder::new(&png).unwrap();
+    let mut chunks = decoder.chunks().unwrap();
+    let idat = chunks.next_chunk().unwrap();
+    assert_eq!(idat.chunk_type(), FourCC::IDAT);
+    assert!(!idat.crc_matches_computed());
+}
+
+#[test]
+fn image_info_from_ihdr_data() {
+    let info = ImageInfo::from_ihdr_data(&ihdr(640, 480, 16, 6)).unwrap();
+    assert_eq!((info.width, info.height), (640, 480));
+    assert_eq!(info.bit_depth, BitDepth::Bpp16);
+    assert_eq!(info.image_type, ImageType::RGBA);
+    assert_eq!(info.raw_color_type(), 6);
+    assert_eq!(info.interlace_method, 0);
+
+    assert_eq!(
+        ImageInfo::from_ihdr_data(&ihdr(0, 480, 8, 0)),
+        Err(DecodeError::InvalidData)
+    );
+    let mut data = ihdr(1, 1, 8, 0);
+    data[12] = 1;
+    assert_eq!(
+        ImageInfo::from_ihdr_data(&data),
+        Err(DecodeError::UnsupportedInterlaceMethod(1))
+    );
+}
+
+#[test]
+fn stride_for_valid_combinations() {
+    let image_types = [
+        (0, ImageType::Grayscale),
+        (2, ImageType::RGB),
+        (3, ImageType::Indexed),
+        (4, ImageType::GrayscaleAlpha),
+        (6, ImageType::RGBA),
+    ];
+    for (color_type, image_type) in image_types {
+        for depth in [1, 2, 4, 8, 16] {
+            let bit_depth = BitDepth::new(depth).unwrap();
+            let result = ImageInfo::from_ihdr_data(&ihdr(13, 1, depth, color_type));
+            if !image_type.is_valid_bit_depth(bit_depth) {
+                assert_eq!(
+                    result,
+                    Err(DecodeError::InvalidBitDepth {
+                        color_type,
+                        bit_depth: depth
+                    })
+                );
+                continue;
+            }
+            let info = result.unwrap();
+            assert_eq!(info.image_type, image_type);
+            let bits = 13 * image_type.n_channels() * depth as usize;
+            assert_eq!(info.stride(), bits.div_ceil(8));
+            if depth >= 8 {
+                assert_eq!(
+                    info.stride(),
+                    13 * image_type.n_channels() * depth as usize / 8
+                );
+            } else {
+                assert_eq!(image_type.n_channels(), 1);
+            }
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn stride_for_invalid_combination() {
+    let mut info = ImageInfo::from_ihdr_data(&ihdr(1, 1, 8, 6)).unwrap();
+    info.bit_depth = BitDepth::Bpp4;
+    info.stride();
+}
+
+#[test]
+fn gray_over_checkerboard() {
+    // transparent, opaque 0x40, transparent, half transparent black
+    let png = make_png(
+        2,
+        2,
+        8,
+        4,
+        &[],
+        &[0, 0x80, 0, 0x40, 0xFF, 0, 0x80, 0, 0, 0x80],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let rgb = decoded.to_gray_over_checkerboard(1).unwrap();
+    assert_eq!(
+        rgb,
+        [
+            0xFF, 0xFF, 0xFF, 0x40, 0x40, 0x40, 0xCC, 0xCC, 0xCC, 0x7F, 0x7F, 0x7F
+        ]
+    );
+
+    // a cell larger than the image is a single white cell
+    let rgb = decoded.to_gray_over_checkerboard(8).unwrap();
+    assert_eq!(&rgb[6..9], [0xFF, 0xFF, 0xFF]);
+
+    let png = make_png(1, 1, 8, 0, &[], &[0, 0]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.to_gray_over_checkerboard(8), None);
+}
+
+#[test]
+fn all_none_filters() {
+    let rows = [[0u8, 1, 2, 3, 4, 5, 6], [0, 7, 8, 9, 10, 11, 12]];
+    let png = make_png(2, 2, 8, 2, &[], &rows.concat());
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), (1..=12).collect::<Vec<u8>>());
+
+    // the same image with the second line filtered by Up
+    let png = make_png(2, 2, 8, 2, &[], &[rows[0], [2, 6, 6, 6, 6, 6, 6]].concat());
+    let filtered = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(filtered.raw_data(), decoded.raw_data());
+
+    // 16-bit and sub-8-bit images take the same path
+    let png = make_png(1, 1, 16, 0, &[], &[0, 0x12, 0x34]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data_u16(), Some(&[0x1234][..]));
+    let png = make_png(4, 1, 2, 0, &[], &[0, 0b00_01_10_11]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data(), [0, 85, 170, 255]);
+}
+
+#[test]
+fn channel_histograms() {
+    let png = make_png(3, 1, 8, 2, &[], &[0, 255, 0, 0, 255, 0, 0, 0, 0, 255]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let [r, g, b, a] = decoded.channel_histograms();
+    assert_eq!((r[255], r[0]), (2, 1));
+    assert_eq!(g[0], 3);
+    assert_eq!((b[255], b[0]), (1, 2));
+    assert_eq!(a[255], 3);
+    for histogram in [r, g, b, a] {
+        assert_eq!(histogram.iter().sum::<u32>(), 3);
+    }
+}
+
+#[test]
+fn decode_checked() {
+    let mut png = make_png(2, 1, 8, 0, &[(b"tEXt", b"Comment\0hello")], &[0, 10, 20]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let all = [
+        FourCC::IHDR,
+        FourCC::tRNS,
+        FourCC(*b"tEXt"),
+        FourCC::IDAT,
+        FourCC::IEND,
+    ];
+    assert_eq!(decoder.decode_checked(&all).unwrap().raw_data(), [10, 20]);
+
+    // break the CRC of the tEXt chunk, right after the IHDR chunk
+    let text_crc = 8 + 25 + 8 + 13;
+    png[text_crc] ^= 1;
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(
+        decoder.decode_checked(&all).unwrap_err(),
+        DecodeError::CrcMismatch
+    );
+    let decoded = decoder
+        .decode_checked(&[FourCC::IHDR, FourCC::IDAT])
+        .unwrap();
+    assert_eq!(decoded.raw_data(), [10, 20]);
+    assert!(decoder.decode_checked(&[]).is_ok());
+}
+
+#[test]
+fn filter_type_round_trip() {
+    for value in 0..5 {
+        assert_eq!(FilterType::new(value).unwrap().as_u8(), value);
+    }
+    assert_eq!(FilterType::new(5), None);
+}
+
+#[test]
+fn raw_data_u16() {
+    let png = make_png(3, 1, 16, 0, &[], &[0, 0x12, 0x34, 0xFF, 0xFE, 0x00, 0x01]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data_u16(), Some(&[0x1234, 0xFFFE, 0x0001][..]));
+    assert_eq!(decoded.raw_data(), [0x12, 0xFF, 0x00]);
+
+    let cropped = decoded.crop(1, 0, 2, 1).unwrap();
+    assert_eq!(cropped.raw_data_u16(), Some(&[0xFFFE, 0x0001][..]));
+    assert_eq!(decoded.into_rgba().raw_data_u16(), None);
+
+    let png = make_png(1, 1, 8, 0, &[], &[0, 0]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.raw_data_u16(), None);
+}
+
+#[test]
+fn swap_rb() {
+    let png = make_png(2, 1, 8, 6, &[], &[0, 255, 0, 0, 255, 1, 2, 3, 4]);
+    let mut decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    decoded.swap_rb();
+    assert_eq!(decoded.raw_data(), [0, 0, 255, 255, 3, 2, 1, 4]);
+
+    let palette = [255, 0, 0];
+    let png = make_png(1, 1, 8, 3, &[(b"PLTE", &palette)], &[0, 0]);
+    let mut decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    decoded.swap_rb();
+    assert_eq!(decoded.palette().unwrap(), [RGB888::new(0, 0, 255)]);
+    assert_eq!(decoded.raw_data(), [0]);
+}
+
+#[test]
+fn compression_ratio() {
+    // zlib stream of a 64x64 black RGB image, (1 + 64 * 3) * 64 zero bytes
+    let compressed = [
+        0x78, 0xDA, 0xED, 0xC1, 0x01, 0x0D, 0x00, 0x00, 0x00, 0xC2, 0xA0, 0xF7, 0x4F, 0x6D, 0x0F,
+        0x07, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x6E,
+        0x30, 0x40, 0x00, 0x01,
+    ];
+    let mut png = Vec::new();
+    png.extend_from_slice(PNG_SIGNATURE);
+    push_chunk(&mut png, b"IHDR", &ihdr(64, 64, 8, 2));
+    push_chunk(&mut png, b"IDAT", &compressed);
+    push_chunk(&mut png, b"IEND", &[]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.compression_ratio().unwrap(), 12352.0 / 34.0);
+    assert!(decoder.decode().unwrap().raw_data().iter().all(|&v| v == 0));
+
+    // stored blocks are slightly larger than the stream
+    let png = make_png(2, 1, 8, 0, &[], &[0, 10, 20]);
+    assert!(PngDecoder::new(&png).unwrap().compression_ratio().unwrap() < 1.0);
+}
+
+#[test]
+fn rgba_bytes_array() {
+    use color::RGBA8888;
+
+    let color = RGBA8888::from_rgba_bytes([0x12, 0x34, 0x56, 0x78]);
+    assert_eq!(color, RGBA8888::from_rgba(0x12, 0x34, 0x56, 0x78));
+    assert_eq!(
+        (color.r(), color.g(), color.b(), color.a()),
+        (0x12, 0x34, 0x56, 0x78)
+    );
+    assert_eq!(color.to_rgba_bytes_array(), [0x12, 0x34, 0x56, 0x78]);
+    assert_eq!(
+        RGBA8888::from_gray(0x9A).to_rgba_bytes_array(),
+        [0x9A, 0x9A, 0x9A, 0xFF]
+    );
+}
+
+#[test]
+fn decode_observer() {
+    #[derive(Default)]
+    struct Counter {
+        started: Vec<DecodePhase>,
+        finished: Vec<(DecodePhase, usize, usize)>,
+    }
+
+    impl DecodeObserver for Counter {
+        fn phase_started(&mut self, phase: DecodePhase) {
+            self.started.push(phase);
+        }
+
+        fn phase_finished(&mut self, phase: DecodePhase, input_bytes: usize, output_bytes: usize) {
+            self.finished.push((phase, input_bytes, output_bytes));
+        }
+    }
+
+    let png = make_png(2, 1, 8, 0, &[], &[0, 10, 20]);
+    let mut counter = Counter::default();
+    let decoded = PngDecoder::new(&png)
+        .unwrap()
+        .decode_with_observer(&mut counter)
+        .unwrap();
+    assert_eq!(decoded.raw_data(), [10, 20]);
+    assert_eq!(
+        counter.started,
+        [DecodePhase::Inflate, DecodePhase::Reconstruct]
+    );
+    let idat_len = zlib_stored(&[0, 10, 20]).len();
+    assert_eq!(
+        counter.finished,
+        [
+            (DecodePhase::Inflate, idat_len, 3),
+            (DecodePhase::Reconstruct, 3, 2)
+        ]
+    );
+}
+
+#[test]
+fn quantize_to_palette() {
+    let png = make_png(4, 1, 8, 0, &[], &[0, 0, 127, 128, 255]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let palette = [RGB888::new(0, 0, 0), RGB888::new(255, 255, 255)];
+    assert_eq!(decoded.quantize_to_palette(&palette), [0, 0, 1, 1]);
+    assert_eq!(decoded.quantize_to_palette(&[]), [0, 0, 0, 0]);
+
+    // equal distances resolve to the lower index
+    let palette = [RGB888::new(0, 0, 0), RGB888::new(0, 0, 0)];
+    assert_eq!(decoded.quantize_to_palette(&palette), [0, 0, 0, 0]);
+}
+
+#[test]
+fn chunk_is_empty() {
+    let png = make_png(1, 1, 8, 0, &[], &[0, 0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let mut chunks = decoder.chunks().unwrap();
+    let idat = chunks.next_chunk().unwrap();
+    assert!(!idat.is_empty());
+    let iend = chunks.next_chunk().unwrap();
+    assert!(iend.is_iend());
+    assert!(iend.is_empty());
+
+    let mut ihdr_chunk = Vec::new();
+    push_chunk(&mut ihdr_chunk, b"IHDR", &ihdr(1, 1, 8, 0));
+    let mut chunks = ChunksInner {
+        iter: ihdr_chunk.iter(),
+        implicit_iend: false,
+    };
+    assert!(!chunks.next_chunk().unwrap().is_empty());
+}
+
+#[test]
+fn suggested_palettes() {
+    let mut splt = b"web\0\x08".to_vec();
+    splt.extend_from_slice(&[255, 0, 0, 255, 0, 10]);
+    splt.extend_from_slice(&[0, 0, 255, 128, 1, 0]);
+    let mut splt16 = b"deep\0\x10".to_vec();
+    splt16.extend_from_slice(&[0x12, 0x34, 0, 0, 0, 0, 0xFF, 0xFF, 0, 1]);
+    let png = make_png(1, 1, 8, 0, &[(b"sPLT", &splt), (b"sPLT", &splt16)], &[0, 0]);
+    let palettes = PngDecoder::new(&png).unwrap().suggested_palettes().unwrap();
+    assert_eq!(palettes.len(), 2);
+    assert_eq!(palettes[0].name, "web");
+    assert_eq!(palettes[0].sample_depth, 8);
+    assert_eq!(palettes[0].entries.len(), 2);
+    assert_eq!(
+        palettes[0].entries[1],
+        SuggestedPaletteEntry {
+            red: 0,
+            green: 0,
+            blue: 255,
+            alpha: 128,
+            frequency: 256
+        }
+    );
+    assert_eq!(palettes[1].entries.len(), 1);
+    assert_eq!(palettes[1].entries[0].red, 0x1234);
+    assert_eq!(palettes[1].entries[0].alpha, 0xFFFF);
+
+    // names must be unique
+    let png = make_png(1, 1, 8, 0, &[(b"sPLT", &splt), (b"sPLT", &splt)], &[0, 0]);
+    assert!(PngDecoder::new(&png).unwrap().suggested_palettes().is_err());
+
+    // a partial entry
+    assert!(SuggestedPalette::parse(&splt[..splt.len() - 1]).is_err());
+}
+
+#[test]
+fn pixel_access() {
+    let png = make_png(
+        2,
+        2,
+        8,
+        2,
+        &[],
+        &[0, 1, 2, 3, 4, 5, 6, 0, 7, 8, 9, 10, 11, 12],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(&decoded[(1, 0)], [4, 5, 6]);
+    assert_eq!(&decoded[(0, 1)], [7, 8, 9]);
+    assert_eq!(
+        decoded.pixel(1, 1),
+        Some(color::RGBA8888::from_rgb(10, 11, 12))
+    );
+    assert_eq!(decoded.pixel(2, 0), None);
+
+    let palette = [0, 0, 0, 255, 128, 0];
+    let png = make_png(2, 1, 1, 3, &[(b"PLTE", &palette)], &[0, 0b01_000000]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(&decoded[(1, 0)], [1]);
+    assert_eq!(
+        decoded.pixel(1, 0),
+        Some(color::RGBA8888::from_rgb(255, 128, 0))
+    );
+}
+
+#[test]
+#[should_panic]
+fn pixel_index_out_of_range() {
+    let png = make_png(2, 1, 8, 0, &[], &[0, 0, 0]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let _ = &decoded[(2, 0)];
+}
+
+#[test]
+fn decode_all() {
+    let mut input = make_png(2, 1, 8, 0, &[], &[0, 10, 20]);
+    // junk between files is skipped
+    input.extend_from_slice(b"\n");
+    input.extend_from_slice(&make_png(1, 1, 8, 2, &[], &[0, 1, 2, 3]));
+    let images = PngDecoder::decode_all(&input)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(images.len(), 2);
+    assert_eq!(images[0].raw_data(), [10, 20]);
+    assert_eq!(images[1].raw_data(), [1, 2, 3]);
+
+    // a broken second file yields an error and stops
+    let mut input = make_png(2, 1, 8, 0, &[], &[0, 10, 20]);
+    input.extend_from_slice(&PNG_SIGNATURE[..]);
+    input.extend_from_slice(&[0; 4]);
+    let results = PngDecoder::decode_all(&input).collect::<Vec<_>>();
+    assert_eq!(results.len(), 2);
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+
+    assert_eq!(PngDecoder::decode_all(&[]).count(), 0);
+}
+
+#[test]
+fn image_info_validate() {
+    let valid = ImageInfo::from_ihdr_data(&ihdr(4, 4, 8, 2)).unwrap();
+    assert_eq!(valid.validate(), Ok(()));
+
+    let mut info = valid;
+    info.image_type = ImageType::Indexed;
+    info.bit_depth = BitDepth::Bpp16;
+    assert_eq!(
+        info.validate(),
+        Err(DecodeError::InvalidBitDepth {
+            color_type: 3,
+            bit_depth: 16
+        })
+    );
+
+    let mut info = valid;
+    info.height = 0;
+    assert_eq!(info.validate(), Err(DecodeError::InvalidData));
+
+    // the color type byte always follows the image type
+    let mut info = valid;
+    info.image_type = ImageType::RGBA;
+    assert_eq!(info.validate(), Ok(()));
+    assert_eq!(info.to_ihdr_data()[9], 6);
+
+    let mut info = valid;
+    info.filter_method = 1;
+    assert_eq!(
+        info.validate(),
+        Err(DecodeError::UnsupportedFilterMethod(1))
+    );
+}
+
+#[test]
+fn rgba_to_be_bytes() {
+    // alpha, blue, green, red
+    let color = color::RGBA8888::from_rgba(0x12, 0x34, 0x56, 0x78);
+    assert_eq!(color.to_be_bytes(), [0x78, 0x56, 0x34, 0x12]);
+    let color = color::RGBA8888::from_rgba(0xFF, 0, 0, 0x80);
+    assert_eq!(color.to_be_bytes(), [0x80, 0, 0, 0xFF]);
+}
+
+#[test]
+fn rgba_to_le_bytes() {
+    // red, green, blue, alpha
+    let color = color::RGBA8888::from_rgba(0x12, 0x34, 0x56, 0x78);
+    assert_eq!(color.to_le_bytes(), [0x12, 0x34, 0x56, 0x78]);
+    assert_eq!(color.to_le_bytes(), color.to_rgba_bytes_array());
+    let color = color::RGBA8888::from_rgba(0xFF, 0, 0, 0x80);
+    assert_eq!(color.to_le_bytes(), [0xFF, 0, 0, 0x80]);
+    assert_eq!(color::RGBA8888::from_rgba_bytes(color.to_le_bytes()), color);
+}
+
+#[test]
+fn unsupported_features() {
+    let info = ImageInfo::from_ihdr_data(&ihdr(4, 4, 16, 2)).unwrap();
+    assert!(info.unsupported_features().is_empty());
+
+    // 16-bit Adam7 interlaced image
+    let mut data = ihdr(4, 4, 16, 2);
+    data[12] = 1;
+    assert_eq!(
+        ImageInfo::from_ihdr_data(&data),
+        Err(DecodeError::UnsupportedInterlaceMethod(1))
+    );
+    let info = ImageInfo::parse_ihdr(&data).unwrap();
+    assert_eq!(info.interlace_method, 1);
+    assert_eq!(info.unsupported_features(), ["Adam7 interlace"]);
+    assert!(ImageInfo::parse_ihdr(&ihdr(0, 4, 16, 2)).is_err());
+    let mut info = info;
+
+    info.bit_depth = BitDepth::Bpp4;
+    info.filter_method = 1;
+    assert_eq!(
+        info.unsupported_features(),
+        [
+            "bit depth for the color type",
+            "filter method",
+            "Adam7 interlace"
+        ]
+    );
+}
+
+#[test]
+fn replace_color() {
+    use color::RGBA8888;
+
+    let magenta = RGBA8888::from_rgb(255, 0, 255);
+    let clear = RGBA8888::from_rgba(0, 0, 0, 0);
+
+    let png = make_png(2, 1, 8, 6, &[], &[0, 255, 0, 255, 255, 1, 2, 3, 255]);
+    let mut decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    decoded.replace_color(magenta, clear);
+    assert_eq!(decoded.raw_data(), [0, 0, 0, 0, 1, 2, 3, 255]);
+
+    let palette = [255, 0, 255, 1, 2, 3];
+    let png = make_png(2, 1, 8, 3, &[(b"PLTE", &palette)], &[0, 0, 1]);
+    let mut decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    decoded.replace_color(magenta, clear);
+    assert_eq!(decoded.transparency(), Some(&[0][..]));
+    assert_eq!(decoded.indices(), Some(&[0, 1][..]));
+    assert_eq!(&decoded.to_rgba_bytes()[..], [0, 0, 0, 0, 1, 2, 3, 255]);
+
+    let png = make_png(
+        2,
+        1,
+        16,
+        6,
+        &[],
+        &[
+            0, 0xFF, 0xFF, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 1, 2, 3, 4, 5, 6, 0xFF, 0xFE,
+        ],
+    );
+    let mut decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    decoded.replace_color(magenta, clear);
+    assert_eq!(decoded.raw_data(), [0, 0, 0, 0, 1, 3, 5, 255]);
+    assert_eq!(
+        decoded.raw_data_u16(),
+        Some(&[0, 0, 0, 0, 0x0102, 0x0304, 0x0506, 0xFFFE][..])
+    );
+}
+
+#[test]
+fn as_gray() {
+    let png = make_png(2, 1, 8, 0, &[], &[0, 10, 20]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let gray = decoded.as_gray().unwrap();
+    assert_eq!(gray, [10, 20]);
+    assert!(core::ptr::eq(gray, decoded.raw_data()));
+
+    let png = make_png(1, 1, 8, 4, &[], &[0, 10, 20]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.as_gray(), None);
+}
+
+#[test]
+fn suggested_palette() {
+    let palette = [1, 2, 3, 4, 5, 6];
+    let png = make_png(1, 1, 8, 2, &[(b"PLTE", &palette)], &[0, 7, 8, 9]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.palette(), None);
+    assert_eq!(
+        decoded.suggested_palette(),
+        Some(&[RGB888::new(1, 2, 3), RGB888::new(4, 5, 6)][..])
+    );
+    assert_eq!(decoded.raw_data(), [7, 8, 9]);
+
+    let png = make_png(1, 1, 8, 2, &[], &[0, 7, 8, 9]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.suggested_palette(), None);
+
+    // the suggested palette must have 1 to 256 entries
+    for len in [0, 257 * 3] {
+        let palette = vec![0; len];
+        let png = make_png(1, 1, 8, 2, &[(b"PLTE", &palette)], &[0, 7, 8, 9]);
+        assert_eq!(
+            PngDecoder::new(&png).unwrap().decode().err(),
+            Some(DecodeError::InvalidPalette)
+        );
+    }
+
+    let png = make_png(1, 1, 8, 3, &[(b"PLTE", &palette)], &[0, 1]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.suggested_palette(), None);
+}
+
+#[test]
+fn be16_round_trip() {
+    for value in [0, 1, 0x1234, 0xFF00, u16::MAX] {
+        let be = Be16::from_u16(value);
+        assert_eq!(be.as_u16(), value);
+        assert_eq!(be.0, value.to_be_bytes());
+    }
+    assert_eq!(Be16([0x12, 0x34]).as_u16(), 0x1234);
+}
+
+#[test]
+fn to_format() {
+    let png = make_png(2, 1, 8, 6, &[], &[0, 10, 20, 30, 255, 40, 50, 60, 128]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    for format in [
+        OutputFormat::Rgb8,
+        OutputFormat::Rgba8,
+        OutputFormat::Bgra8,
+        OutputFormat::Gray8,
+        OutputFormat::GrayAlpha8,
+    ] {
+        let output = decoded.to_format(format);
+        assert_eq!(output.len(), 2 * format.bytes_per_pixel(), "{format:?}");
+    }
+    assert!(matches!(
+        decoded.to_format(OutputFormat::Rgba8),
+        Cow::Borrowed(_)
+    ));
+    assert_eq!(
+        &decoded.to_format(OutputFormat::Bgra8)[..],
+        [30, 20, 10, 255, 60, 50, 40, 128]
+    );
+    assert_eq!(
+        &decoded.to_format(OutputFormat::Rgb8)[..],
+        [10, 20, 30, 40, 50, 60]
+    );
+    let gray_alpha = decoded.to_format(OutputFormat::GrayAlpha8);
+    assert_eq!([gray_alpha[1], gray_alpha[3]], [255, 128]);
+
+    let png = make_png(1, 1, 8, 4, &[], &[0, 10, 20]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert!(matches!(
+        decoded.to_format(OutputFormat::GrayAlpha8),
+        Cow::Borrowed([10, 20])
+    ));
+}
+
+#[test]
+fn huge_chunk_length() {
+    for length in [0xFFFF_FFFFu32, 0x8000_0000, 0x7FFF_FFFF, 1] {
+        let mut data = Vec::new();
+        data.extend_from_slice(&length.to_be_bytes());
+        data.extend_from_slice(b"tEXt");
+        data.extend_from_slice(&[0; 4]);
+        let chunks = ChunksInner {
+            iter: data.iter(),
+            implicit_iend: false,
+        };
+        assert_eq!(chunks.peek_chunk().err(), Some(DecodeError::InvalidData));
+    }
+}
+
+#[test]
+fn stream_encoder() {
+    let png = make_png(1, 1, 8, 2, &[], &[0, 0, 0, 0]);
+    let info = *PngDecoder::new(&png).unwrap().info();
+    let info = ImageInfo {
+        width: 300,
+        height: 200,
+        ..info
+    };
+    let row = |y: usize| {
+        (0..info.stride())
+            .map(|x| (x * 7 + y * 3) as u8)
+            .collect::<Vec<_>>()
+    };
+
+    for filter_type in [FilterType::None, FilterType::Paeth] {
+        let mut output = Vec::new();
+        let mut encoder =
+            PngStreamEncoder::new(info, |bytes: &[u8]| output.extend_from_slice(bytes))
+                .unwrap()
+                .filter_type(filter_type);
+        for y in 0..200 {
+            encoder.write_row(&row(y)).unwrap();
+        }
+        assert_eq!(encoder.write_row(&row(0)), Err(EncodeError::TooManyRows));
+        encoder.finish().unwrap();
+
+        let decoder = PngDecoder::new(&output).unwrap();
+        assert_eq!(decoder.verify(true), Ok(()));
+        assert!(
+            decoder
+                .chunks_unchecked()
+                .filter(|v| v.chunk_type() == FourCC::IDAT)
+                .count()
+                > 1
+        );
+        let decoded = decoder.decode().unwrap();
+        assert_eq!(*decoded.info(), info);
+        for (y, line) in decoded.rows().enumerate() {
+            assert_eq!(line, row(y));
+        }
+    }
+
+    // the image stream is written before the last scanline
+    let mut written = 0;
+    let mut encoder = PngStreamEncoder::new(info, |bytes: &[u8]| written += bytes.len()).unwrap();
+    for y in 0..199 {
+        encoder.write_row(&row(y)).unwrap();
+    }
+    drop(encoder);
+    assert!(written > 8 + 25 + 12);
+
+    // too few rows
+    let mut encoder = PngStreamEncoder::new(info, |_: &[u8]| {}).unwrap();
+    encoder.write_row(&row(0)).unwrap();
+    assert_eq!(encoder.finish(), Err(EncodeError::MissingRows));
+
+    let mut encoder = PngStreamEncoder::new(info, |_: &[u8]| {}).unwrap();
+    assert_eq!(
+        encoder.write_row(&row(0)[1..]),
+        Err(EncodeError::InvalidRowLength)
+    );
+
+    let invalid = ImageInfo { width: 0, ..info };
+    assert_eq!(
+        PngStreamEncoder::new(invalid, |_: &[u8]| {}).err(),
+        Some(EncodeError::InvalidImageInfo)
+    );
+
+    let mut encoder = PngStreamEncoder::new(info, |_: &[u8]| {})
+        .unwrap()
+        .transparency(&[0]);
+    assert_eq!(
+        encoder.write_row(&row(0)),
+        Err(EncodeError::InvalidTransparency)
+    );
+
+    let png = make_png(1, 1, 8, 3, &[(b"PLTE", &[0, 0, 0])], &[0, 0]);
+    let indexed = *PngDecoder::new(&png).unwrap().info();
+    let mut encoder = PngStreamEncoder::new(indexed, |_: &[u8]| {}).unwrap();
+    assert_eq!(encoder.write_row(&[0]), Err(EncodeError::InvalidPalette));
+}
+
+#[test]
+fn decode_with_stats() {
+    let scanlines = [0, 1, 2, 1, 3, 4, 4, 1, 4, 0, 2, 4, 4];
+    let scanlines = scanlines
+        .iter()
+        .flat_map(|&filter| [filter, 0x55])
+        .collect::<Vec<_>>();
+    let png = make_png(1, 13, 8, 0, &[], &scanlines);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let (decoded, stats) = decoder.decode_with_stats().unwrap();
+    assert_eq!(stats.filter_counts, [2, 3, 2, 1, 5]);
+    assert_eq!(stats.filter_count(FilterType::Paeth), 5);
+    assert_eq!(decoded.raw_data(), decoder.decode().unwrap().raw_data());
+}
+
+#[test]
+fn rgb_hex() {
+    let color = RGB888::from_hex("#FF8800").unwrap();
+    assert_eq!(color, RGB888::new(0xFF, 0x88, 0x00));
+    assert_eq!(&color.to_hex(), b"#FF8800");
+    assert_eq!(RGB888::from_hex("#ff8800"), Some(color));
+    assert_eq!(RGB888::new(1, 0xAB, 0x20).to_hex(), *b"#01AB20");
+
+    for invalid in ["FF8800", "#FF880", "#FF88000", "#GG8800", "#+F8800", ""] {
+        assert_eq!(RGB888::from_hex(invalid), None, "{invalid}");
+    }
+}
+
+#[test]
+fn decode_indexed_to_rgb() {
+    let palette = [10, 20, 30, 40, 50, 60, 70, 80, 90];
+    // 4-bit indices, 3x2, with Sub filter on the second line
+    let png = make_png(
+        3,
+        2,
+        4,
+        3,
+        &[(b"PLTE", &palette)],
+        &[0, 0x01, 0x20, 1, 0x21, 0xF0],
+    );
+    let decoder = PngDecoder::new(&png).unwrap();
+    let rgb = decoder.decode_to_rgb().unwrap();
+    assert_eq!(rgb, &decoder.decode().unwrap().to_rgb_bytes()[..]);
+    assert_eq!(&rgb[..9], [10, 20, 30, 40, 50, 60, 70, 80, 90]);
+    assert_eq!(decode_rgb(&png).unwrap().1, rgb);
+
+    // index out of range
+    let png = make_png(2, 1, 8, 3, &[(b"PLTE", &palette)], &[0, 1, 3]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.decode_to_rgb(), Err(DecodeError::InvalidPalette));
+}
+
+#[test]
+fn rotate() {
+    // 2x3 grayscale
+    // 1 2
+    // 3 4
+    // 5 6
+    let png = make_png(2, 3, 8, 0, &[], &[0, 1, 2, 0, 3, 4, 0, 5, 6]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+
+    let rotated = decoded.rotate90();
+    assert_eq!((rotated.info().width, rotated.info().height), (3, 2));
+    assert_eq!(rotated.raw_data(), [5, 3, 1, 6, 4, 2]);
+    assert_eq!(rotated[(0, 0)], [5]);
+
+    let rotated = decoded.rotate180();
+    assert_eq!((rotated.info().width, rotated.info().height), (2, 3));
+    assert_eq!(rotated.raw_data(), [6, 5, 4, 3, 2, 1]);
+
+    let rotated = decoded.rotate270();
+    assert_eq!((rotated.info().width, rotated.info().height), (3, 2));
+    assert_eq!(rotated.raw_data(), [2, 4, 6, 1, 3, 5]);
+    assert_eq!(rotated.rotate90().raw_data(), decoded.raw_data());
+
+    // 16-bit RGB keeps full precision
+    let png = make_png(
+        2,
+        1,
+        16,
+        2,
+        &[],
+        &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let rotated = decoded.rotate90();
+    assert_eq!((rotated.info().width, rotated.info().height), (1, 2));
+    assert_eq!(rotated.raw_data(), decoded.raw_data());
+    assert_eq!(rotated.raw_data_u16(), decoded.raw_data_u16());
+    let rotated = decoded.rotate180();
+    assert_eq!(
+        rotated.raw_data_u16().unwrap(),
+        [0x0708, 0x090A, 0x0B0C, 0x0102, 0x0304, 0x0506]
+    );
+}
+
+#[test]
+fn iter_rgb() {
+    let palette = [1, 2, 3, 4, 5, 6];
+    let sources = [
+        make_png(2, 1, 8, 0, &[], &[0, 10, 20]),
+        make_png(2, 1, 8, 4, &[], &[0, 10, 128, 20, 255]),
+        make_png(1, 1, 8, 2, &[], &[0, 10, 20, 30]),
+        make_png(1, 1, 8, 6, &[], &[0, 10, 20, 30, 0]),
+        make_png(2, 1, 8, 3, &[(b"PLTE", &palette)], &[0, 1, 0]),
+    ];
+    for png in sources {
+        let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+        let image_type = decoded.info().image_type;
+        let rgb = image_type
+            .iter_rgb(decoded.raw_data(), &decoded.palette)
+            .flat_map(|color| [color.r, color.g, color.b])
+            .collect::<Vec<_>>();
+        assert_eq!(rgb, &decoded.to_rgb_bytes()[..], "{image_type}");
+    }
+}
+
+#[test]
+fn strict_idat() {
+    let mut stream = zlib_stored(&[0, 1, 2]);
+    let second = stream.split_off(4);
+    let mut png = Vec::new();
+    png.extend_from_slice(PNG_SIGNATURE);
+    push_chunk(&mut png, b"IHDR", &ihdr(2, 1, 8, 0));
+    push_chunk(&mut png, b"IDAT", &stream);
+    push_chunk(&mut png, b"tEXt", b"Comment\0between");
+    push_chunk(&mut png, b"IDAT", &second);
+    push_chunk(&mut png, b"IEND", &[]);
+
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert!(!decoder.is_strict_idat());
+    assert_eq!(decoder.decode().unwrap().raw_data(), [1, 2]);
+
+    let decoder = decoder.strict_idat(true);
+    assert!(decoder.is_strict_idat());
+    assert_eq!(decoder.decode().err(), Some(DecodeError::InvalidData));
+    assert_eq!(decoder.verify(false), Err(DecodeError::InvalidData));
+
+    // consecutive IDAT chunks are accepted
+    let png = make_png(2, 1, 8, 0, &[(b"tEXt", b"Comment\0before")], &[0, 1, 2]);
+    let decoder = PngDecoder::new(&png).unwrap().strict_idat(true);
+    assert_eq!(decoder.decode().unwrap().raw_data(), [1, 2]);
+    assert_eq!(decoder.verify(true), Ok(()));
+}
+
+#[test]
+fn encoder_filter_strategy() {
+    let palette = [0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255];
+    let sources = [
+        make_png(
+            3,
+            2,
+            8,
+            6,
+            &[],
+            &[
+                0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 2, 1, 1, 1, 1, 2, 3, 4, 5, 6, 7, 8, 9,
+            ],
+        ),
+        make_png(5, 2, 4, 0, &[], &[0, 0x12, 0x34, 0x50, 1, 0xF1, 0x11, 0x10]),
+        make_png(
+            5,
+            2,
+            2,
+            3,
+            &[(b"PLTE", &palette), (b"tRNS", &[0, 128])],
+            &[0, 0x1B, 0x40, 4, 0xE4, 0x00],
+        ),
+        make_png(2, 1, 16, 0, &[], &[0, 0x12, 0x34, 0xFE, 0xDC]),
+    ];
+    for png in sources {
+        let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+        for filter in [
+            FilterStrategy::Fixed(FilterType::Sub),
+            FilterStrategy::Fixed(FilterType::Paeth),
+            FilterStrategy::MinSumAbs,
+        ] {
+            let encoder = PngEncoder::with_filter(filter);
+            assert_eq!(encoder.filter(), filter);
+            let encoded = encoder.encode(&decoded).unwrap();
+            let decoder = PngDecoder::new(&encoded).unwrap();
+            assert_eq!(*decoder.info(), *decoded.info());
+            let (reencoded, stats) = decoder.decode_with_stats().unwrap();
+            if let FilterStrategy::Fixed(filter_type) = filter {
+                assert_eq!(
+                    stats.filter_count(filter_type),
+                    decoded.info().height as usize
+                );
+            }
+            assert_eq!(reencoded.raw_data(), decoded.raw_data());
+            assert_eq!(reencoded.raw_data_u16(), decoded.raw_data_u16());
+            assert_eq!(reencoded.palette(), decoded.palette());
+            assert_eq!(reencoded.transparency(), decoded.transparency());
+        }
+    }
+}
+
+#[test]
+fn encoder_compression() {
+    let png = make_png(1, 1, 8, 2, &[], &[0, 0, 0, 0]);
+    let info = ImageInfo {
+        width: 256,
+        height: 256,
+        ..*PngDecoder::new(&png).unwrap().info()
+    };
+    let encode = |filter: FilterStrategy, row: &dyn Fn(usize) -> Vec<u8>| {
+        let mut output = Vec::new();
+        let mut encoder =
+            PngStreamEncoder::new(info, |bytes: &[u8]| output.extend_from_slice(bytes))
+                .unwrap()
+                .filter_strategy(filter);
+        for y in 0..256 {
+            encoder.write_row(&row(y)).unwrap();
+        }
+        encoder.finish().unwrap();
+        let decoded = PngDecoder::new(&output).unwrap().decode().unwrap();
+        for (y, line) in decoded.rows().enumerate() {
+            assert_eq!(line, row(y));
+        }
+        output.len()
+    };
+
+    // a gradient with a different slope in each row compresses much better after filtering
+    let gradient = |y: usize| {
+        (0..info.stride())
+            .map(|x| ((x / 3) * (y + 1) / 4 + x % 3 * 85) as u8)
+            .collect::<Vec<_>>()
+    };
+    let none = encode(FilterStrategy::Fixed(FilterType::None), &gradient);
+    let sub = encode(FilterStrategy::Fixed(FilterType::Sub), &gradient);
+    let min_sum_abs = encode(FilterStrategy::MinSumAbs, &gradient);
+    assert!(none < info.stride() * 256);
+    assert!(sub < none / 2);
+    assert!(min_sum_abs < none);
+
+    // incompressible data still round-trips
+    let noise = |y: usize| {
+        (0..info.stride())
+            .map(|x| {
+                let v = ((y * info.stride() + x) as u32).wrapping_mul(0x9E37_79B9);
+                (v >> 24) as u8 ^ (v >> 13) as u8
+            })
+            .collect::<Vec<_>>()
+    };
+    encode(FilterStrategy::MinSumAbs, &noise);
+}
+
+#[test]
+fn is_png_signature() {
+    let png = make_png(1, 1, 8, 0, &[], &[0, 0]);
+    assert!(is_png(&png));
+    assert!(is_png(PNG_SIGNATURE));
+    assert!(!is_png(&[
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F'
+    ]));
+    assert!(!is_png(&PNG_SIGNATURE[..7]));
+    assert!(!is_png(&[]));
+}
+
+#[test]
+fn alpha_mask() {
+    let png = make_png(2, 1, 8, 6, &[], &[0, 10, 20, 30, 40, 50, 60, 70, 80]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let expected = decoded
+        .raw_data()
+        .iter()
+        .skip(3)
+        .step_by(4)
+        .copied()
+        .collect::<Vec<_>>();
+    assert_eq!(decoded.alpha_mask(), Some(expected));
+
+    let png = make_png(2, 1, 8, 4, &[], &[0, 10, 20, 30, 40]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.alpha_mask(), Some(vec![20, 40]));
+
+    let palette = [1, 2, 3, 4, 5, 6];
+    let png = make_png(
+        2,
+        1,
+        8,
+        3,
+        &[(b"PLTE", &palette), (b"tRNS", &[0])],
+        &[0, 0, 1],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.alpha_mask(), Some(vec![0, 255]));
+
+    let png = make_png(2, 1, 8, 3, &[(b"PLTE", &palette)], &[0, 0, 1]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.alpha_mask(), None);
+
+    let png = make_png(1, 1, 8, 2, &[], &[0, 1, 2, 3]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.alpha_mask(), None);
+}
+
+#[test]
+fn decode_with_palette() {
+    let palette = [255, 0, 0, 0, 255, 0];
+    let png = make_png(3, 1, 8, 3, &[(b"PLTE", &palette)], &[0, 0, 1, 0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+
+    let swapped = [RGB888::new(0, 0, 255), RGB888::new(255, 255, 0)];
+    let decoded = decoder.decode_with_palette(&swapped).unwrap();
+    assert_eq!(decoded.indices(), Some(&[0, 1, 0][..]));
+    assert_eq!(decoded.palette(), Some(&swapped[..]));
+    assert_eq!(
+        decoded.pixel(1, 0),
+        Some(color::RGBA8888::from_rgb(255, 255, 0))
+    );
+    assert_eq!(&decoded.to_rgb_bytes()[..3], [0, 0, 255]);
+
+    // the substitute must cover the indices used
+    assert_eq!(
+        decoder.decode_with_palette(&swapped[..1]).err(),
+        Some(DecodeError::InvalidPalette)
+    );
+
+    // the substitute may be larger than the bit depth can address
+    let png = make_png(2, 1, 1, 3, &[(b"PLTE", &palette)], &[0, 0b0100_0000]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let gray = (0..=255).map(|v| RGB888::new(v, v, v)).collect::<Vec<_>>();
+    let decoded = decoder.decode_with_palette(&gray).unwrap();
+    assert_eq!(decoded.indices(), Some(&[0, 1][..]));
+    assert_eq!(decoded.palette().map(|v| v.len()), Some(256));
+
+    let png = make_png(1, 1, 8, 0, &[], &[0, 0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(
+        decoder.decode_with_palette(&swapped).err(),
+        Some(DecodeError::UnsupportedFormat)
+    );
+}
+
+#[test]
+fn bit_depth_scaling() {
+    let max_values = [1, 3, 15, 255, 65535];
+    let depths = [
+        BitDepth::Bpp1,
+        BitDepth::Bpp2,
+        BitDepth::Bpp4,
+        BitDepth::Bpp8,
+        BitDepth::Bpp16,
+    ];
+    for (bit_depth, max_value) in depths.into_iter().zip(max_values) {
+        assert_eq!(bit_depth.max_value(), max_value);
+        assert_eq!(bit_depth.scale_to_8bit(max_value), 255, "{bit_depth}");
+        assert_eq!(bit_depth.scale_to_8bit(0), 0, "{bit_depth}");
+    }
+    assert_eq!(BitDepth::Bpp4.scale_to_8bit(15), 255);
+    assert_eq!(BitDepth::Bpp4.scale_to_8bit(1), 17);
+    assert_eq!(BitDepth::Bpp2.scale_to_8bit(2), 170);
+    assert_eq!(BitDepth::Bpp8.scale_to_8bit(0x7F), 0x7F);
+    assert_eq!(BitDepth::Bpp16.scale_to_8bit(0x12FF), 0x12);
+}
+
+#[test]
+fn single_idat_is_borrowed() {
+    let png = make_png(2, 1, 8, 0, &[(b"tEXt", b"Comment\0x")], &[0, 1, 2]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let idat = decoder.chunks_unchecked().get_idat_chunks(false).unwrap();
+    assert!(matches!(idat, Cow::Borrowed(_)));
+    assert_eq!(idat, zlib_stored(&[0, 1, 2]));
+
+    let stream = zlib_stored(&[0, 1, 2]);
+    let mut png = Vec::new();
+    png.extend_from_slice(PNG_SIGNATURE);
+    push_chunk(&mut png, b"IHDR", &ihdr(2, 1, 8, 0));
+    push_chunk(&mut png, b"IDAT", &stream[..4]);
+    push_chunk(&mut png, b"IDAT", &stream[4..]);
+    push_chunk(&mut png, b"IEND", &[]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let idat = decoder.chunks_unchecked().get_idat_chunks(false).unwrap();
+    assert!(matches!(idat, Cow::Owned(_)));
+    assert_eq!(idat, stream);
+}
+
+#[test]
+fn gif_extensions() {
+    let png = make_png(
+        1,
+        1,
+        8,
+        0,
+        &[
+            (b"gIFg", &[2, 1, 0x01, 0x2C]),
+            (b"gIFx", b"NETSCAPE2.0\x03\x01\x00\x00"),
+        ],
+        &[0, 0],
+    );
+    let decoder = PngDecoder::new(&png).unwrap();
+    let extensions = decoder.gif_extensions().unwrap();
+    assert_eq!(
+        extensions.graphic_controls,
+        [GifGraphicControl {
+            disposal_method: 2,
+            user_input: true,
+            delay_time: 300,
+        }]
+    );
+    assert_eq!(extensions.applications.len(), 1);
+    assert_eq!(&extensions.applications[0].identifier, b"NETSCAPE");
+    assert_eq!(&extensions.applications[0].authentication_code, b"2.0");
+    assert_eq!(extensions.applications[0].data, [3, 1, 0, 0]);
+    assert!(decoder.unknown_chunks().unwrap().is_empty());
+
+    assert_eq!(
+        GifGraphicControl::parse(&[0, 0, 1]),
+        Err(DecodeError::InvalidData)
+    );
+    assert_eq!(
+        GifApplicationExtension::parse(b"NETSCAPE2."),
+        Err(DecodeError::InvalidData)
+    );
+}
+
+#[test]
+fn resize_bilinear() {
+    // 2x2 checkerboard
+    let png = make_png(2, 2, 8, 0, &[], &[0, 0, 255, 0, 255, 0]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    let resized = decoded.resize_bilinear(4, 4);
+    assert_eq!((resized.info().width, resized.info().height), (4, 4));
+    assert_eq!(resized.info().image_type, ImageType::RGBA);
+    // corners keep the source colors
+    assert_eq!(resized.pixel(0, 0), Some(color::RGBA8888::from_gray(0)));
+    assert_eq!(resized.pixel(3, 0), Some(color::RGBA8888::from_gray(255)));
+    assert_eq!(resized.pixel(3, 3), Some(color::RGBA8888::from_gray(0)));
+    // 0.25 of the way to each neighbor: 255 * (2 * 0.25 * 0.75)
+    assert_eq!(resized.pixel(1, 1), Some(color::RGBA8888::from_gray(96)));
+    // on the top edge, a quarter of the way from black to white
+    assert_eq!(resized.pixel(1, 0), Some(color::RGBA8888::from_gray(64)));
+
+    let same = decoded.resize_bilinear(2, 2);
+    assert_eq!(same.raw_data(), &decoded.to_rgba_bytes()[..]);
+    let shrunk = decoded.resize_bilinear(1, 1);
+    assert_eq!(shrunk.pixel(0, 0), Some(color::RGBA8888::from_gray(128)));
+}
+
+#[test]
+fn decode_with_chunk_list() {
+    let png = make_png(2, 1, 8, 0, &[(b"tEXt", b"Comment\0x")], &[0, 1, 2]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let (decoded, chunks) = decoder.decode_with_chunk_list().unwrap();
+    assert_eq!(decoded.raw_data(), [1, 2]);
+    let idat_len = zlib_stored(&[0, 1, 2]).len();
+    assert_eq!(
+        chunks,
+        [
+            (FourCC::IHDR, 13),
+            (FourCC(*b"tEXt"), 9),
+            (FourCC::IDAT, idat_len),
+            (FourCC::IEND, 0),
+        ]
+    );
+}
+
+#[test]
+fn background() {
+    use color::RGBA8888;
+
+    let png = make_png(
+        1,
+        1,
+        8,
+        2,
+        &[(b"bKGD", &[0, 0x12, 0, 0x34, 0, 0x56])],
+        &[0, 1, 2, 3],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(
+        decoded.background(),
+        Some(RGBA8888::from_rgb(0x12, 0x34, 0x56))
+    );
+    // the pixels are untouched
+    assert_eq!(decoded.raw_data(), [1, 2, 3]);
+    assert_eq!(
+        decoded.crop(0, 0, 1, 1).unwrap().background(),
+        decoded.background()
+    );
+
+    // 4-bit grayscale is scaled like the samples
+    let png = make_png(2, 1, 4, 0, &[(b"bKGD", &[0, 5])], &[0, 0x12]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.background(), Some(RGBA8888::from_gray(0x55)));
+
+    let palette = [1, 2, 3, 4, 5, 6];
+    let png = make_png(1, 1, 8, 3, &[(b"PLTE", &palette), (b"bKGD", &[1])], &[0, 0]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.background(), Some(RGBA8888::from_rgb(4, 5, 6)));
+    assert_eq!(
+        decoded.into_rgba().background(),
+        Some(RGBA8888::from_rgb(4, 5, 6))
+    );
+
+    let png = make_png(1, 1, 8, 0, &[], &[0, 0]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.background(), None);
+
+    // out of range index, wrong length: the background is dropped but decoding goes on
+    let png = make_png(1, 1, 8, 3, &[(b"PLTE", &palette), (b"bKGD", &[2])], &[0, 0]);
+    let decoder = PngDecoder::new(&png).unwrap();
+    let decoded = decoder.decode().unwrap();
+    assert_eq!(decoded.background(), None);
+    assert_eq!(decoded.indices(), Some(&[0][..]));
+    assert_eq!(decoder.decode_to_rgb().unwrap(), [1, 2, 3]);
+    assert!(decoder.palette().is_ok());
+    let png = make_png(1, 1, 8, 0, &[(b"bKGD", &[0])], &[0, 7]);
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.background(), None);
+    assert_eq!(decoded.raw_data(), [7]);
+}
+
+#[test]
+fn malformed_input_never_panics() {
+    let palette = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+    let sources = [
+        make_png(3, 2, 8, 6, &[], &[0; 26]),
+        make_png(
+            5,
+            2,
+            2,
+            3,
+            &[(b"PLTE", &palette), (b"tRNS", &[0])],
+            &[1, 0x1B, 0x40, 4, 0xE4, 0x00],
+        ),
+        make_png(
+            2,
+            2,
+            16,
+            0,
+            &[(b"bKGD", &[0, 1])],
+            &[2, 1, 2, 3, 4, 3, 5, 6, 7, 8],
+        ),
+        make_png(9, 1, 1, 0, &[], &[0, 0xAA, 0x80]),
+    ];
+    let exercise = |input: &[u8]| {
+        let Ok(decoder) = PngDecoder::new(input) else {
+            return false;
+        };
+        let _ = decoder.verify(true);
+        let _ = decoder.decode_partial();
+        let _ = decoder.decode_to_rgb();
+        let _ = decoder.unknown_chunks();
+        let _ = decoder.gif_extensions();
+        let Ok(decoded) = decoder.decode() else {
+            return false;
+        };
+        let _ = decoded.to_rgba_bytes();
+        let _ = decoded.to_rgb_bytes();
+        let _ = decoded.to_gray_bytes();
+        let _ = decoded.pixel(0, 0);
+        true
+    };
+
+    // simple deterministic pseudo random numbers
+    let mut seed = 0x1234_5678u32;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed
+    };
+
+    for png in sources {
+        assert!(exercise(&png));
+        // any truncation is an error
+        for len in 0..png.len() {
+            assert!(!exercise(&png[..len]), "truncated to {len}");
+        }
+        // corrupted bytes, including the IHDR chunk
+        for i in 8..png.len() {
+            for xor in [0x01, 0x80, 0xFF] {
+                let mut input = png.clone();
+                input[i] ^= xor;
+                exercise(&input);
+            }
+        }
+        // garbage after the IHDR chunk
+        for _ in 0..200 {
+            let mut input = png[..33].to_vec();
+            let len = random() % 64;
+            input.extend((0..len).map(|_| random() as u8));
+            exercise(&input);
+        }
+        // extreme sizes, the CRC of the IHDR chunk is not checked by the decoder
+        let sizes = [0, 0x7FFF_FFFF, 0x8000_0000, 0xFFFF_FFFF];
+        for width in sizes {
+            for height in sizes {
+                let mut input = png.clone();
+                input[16..20].copy_from_slice(&u32::to_be_bytes(width));
+                input[20..24].copy_from_slice(&u32::to_be_bytes(height));
+                assert!(!exercise(&input), "{width} x {height}");
+                let _ = PngDecoder::new(&input).map(|decoder| decoder.compression_ratio());
+            }
+        }
+    }
+
+    // indices outside the palette do not panic in the conversions
+    let image_type = ImageType::Indexed;
+    let colors = image_type
+        .iter(&[0, 7], &[RGB888::new(1, 2, 3)])
+        .collect::<Vec<_>>();
+    assert_eq!(colors[1], color::RGBA8888::from_rgb(0, 0, 0));
+    assert_eq!(
+        image_type.iter_rgb(&[9], &[]).next(),
+        Some(RGB888::new(0, 0, 0))
+    );
+}
+
+#[test]
+fn short_sub_8bit_image_stream() {
+    let palette = [1, 2, 3, 4, 5, 6];
+    for bit_depth in [1, 2, 4] {
+        // the second scanline is one byte short
+        let stride = (9 * bit_depth as usize).div_ceil(8);
+        let mut scanlines = vec![0; 1 + stride];
+        scanlines.extend(vec![0; stride]);
+        let png = make_png(9, 2, bit_depth, 0, &[], &scanlines);
+        let decoder = PngDecoder::new(&png).unwrap();
+        assert_eq!(decoder.decode().err(), Some(DecodeError::InvalidData));
+        let (_, valid_rows) = decoder.decode_partial().unwrap();
+        assert_eq!(valid_rows, 1);
+
+        let png = make_png(9, 2, bit_depth, 3, &[(b"PLTE", &palette)], &scanlines);
+        let decoder = PngDecoder::new(&png).unwrap();
+        assert_eq!(decoder.decode().err(), Some(DecodeError::InvalidData));
+        assert_eq!(
+            decoder.decode_to_rgb().err(),
+            Some(DecodeError::InvalidData)
+        );
+    }
+}
+
+#[test]
+fn rgb565() {
+    let png = make_png(
+        3,
+        1,
+        8,
+        6,
+        &[],
+        &[0, 255, 0, 0, 255, 0, 255, 0, 0, 0x12, 0x34, 0x56, 0x80],
+    );
+    let decoded = PngDecoder::new(&png).unwrap().decode().unwrap();
+    assert_eq!(decoded.to_rgb565(), [0xF800, 0x07E0, 0x11AA]);
+    assert_eq!(
+        decoded.to_rgb565_be_bytes(),
+        [0xF8, 0x00, 0x07, 0xE0, 0x11, 0xAA]
+    );
+    assert_eq!(
+        decoded.to_rgb565_le_bytes(),
+        [0x00, 0xF8, 0xE0, 0x07, 0xAA, 0x11]
+    );
+    assert_eq!(RGB888::new(255, 255, 255).to_rgb565(), 0xFFFF);
+}
+
+#[test]
+fn image_size_limits() {
+    // the specification limits the width and height to 2^31-1
+    assert!(ImageInfo::parse_ihdr(&ihdr(0x7FFF_FFFF, 1, 8, 0)).is_ok());
+    for (width, height) in [(0x8000_0000, 1), (1, 0x8000_0000), (u32::MAX, u32::MAX)] {
+        let data = ihdr(width, height, 8, 0);
+        assert_eq!(
+            ImageInfo::parse_ihdr(&data).err(),
+            Some(DecodeError::InvalidData)
+        );
+        assert_eq!(
+            ImageInfo::from_ihdr_data(&data).err(),
+            Some(DecodeError::InvalidData)
+        );
+    }
+
+    // sizes that do not fit in memory are rejected instead of overflowing
+    let info = ImageInfo::parse_ihdr(&ihdr(0x7FFF_FFFF, 0x7FFF_FFFF, 16, 6)).unwrap();
+    assert_eq!(info.validate(), Err(DecodeError::UnsupportedFormat));
+    let png = make_png(0x7FFF_FFFF, 0x7FFF_FFFF, 16, 6, &[], &[0; 64]);
+    assert_eq!(
+        PngDecoder::new(&png).err(),
+        Some(DecodeError::UnsupportedFormat)
+    );
+}
+
+#[test]
+fn huge_declared_size_does_not_allocate() {
+    // a tiny file declaring 0x7FFFFFFF x 0x10000 RGBA pixels
+    let png = make_png(0x7FFF_FFFF, 0x1_0000, 8, 6, &[], &[0; 64]);
+    assert!(png.len() < 150);
+    let decoder = PngDecoder::new(&png).unwrap();
+    assert_eq!(decoder.decode().err(), Some(DecodeError::InvalidData));
+    assert_eq!(
+        decoder.decode_with_stats().err(),
+        Some(DecodeError::InvalidData)
+    );
+    assert_eq!(
+        decoder.decode_partial().err(),
+        Some(DecodeError::OutOfMemory)
+    );
+}